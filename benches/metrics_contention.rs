@@ -0,0 +1,86 @@
+//! Contention benchmark: the name-keyed `MetricsCollector` API against
+//! pre-registered handles, with several threads recording to one series.
+//!
+//! Run with `cargo bench --bench metrics_contention`. Set `THREADS` and
+//! `OPS` to change the load (defaults 8 and 200000 per thread).
+
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+use sigma_telemetry::config::TelemetryConfig;
+use sigma_telemetry::metrics::MetricNames;
+use sigma_telemetry::SigmaTelemetry;
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Run `op(thread, i)` `ops` times on each of `threads` threads started
+/// together, returning the wall time
+fn run(threads: usize, ops: usize, op: impl Fn(usize, usize) + Send + Sync + 'static) -> Duration {
+    let op = Arc::new(op);
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let (op, barrier) = (Arc::clone(&op), Arc::clone(&barrier));
+            std::thread::spawn(move || {
+                barrier.wait();
+                for i in 0..ops {
+                    op(t, i);
+                }
+            })
+        })
+        .collect();
+    barrier.wait();
+    let start = Instant::now();
+    handles.into_iter().for_each(|h| h.join().unwrap());
+    start.elapsed()
+}
+
+fn report(name: &str, threads: usize, ops: usize, baseline: Duration, handle: Duration) {
+    let per_op = |d: Duration| d.as_nanos() as f64 / (threads * ops) as f64;
+    println!(
+        "{name:<10} collector {:>8.1} ns/op   handle {:>8.1} ns/op   speedup {:>5.1}x",
+        per_op(baseline),
+        per_op(handle),
+        baseline.as_secs_f64() / handle.as_secs_f64()
+    );
+}
+
+fn main() {
+    let threads = env_or("THREADS", 8);
+    let ops = env_or("OPS", 200_000);
+    println!("{threads} threads x {ops} ops, one shared series");
+    let model = [("model", "bitnet-3b")];
+
+    let t = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+    let baseline = {
+        let t = Arc::clone(&t);
+        run(threads, ops, move |_, _| t.metrics().increment_with(MetricNames::INFERENCE_TOKENS, &model))
+    };
+    let counter = t.metrics().counter_with(MetricNames::INFERENCE_TOKENS, &model);
+    let handle = run(threads, ops, move |_, _| counter.increment());
+    report("counter", threads, ops, baseline, handle);
+
+    let baseline = {
+        let t = Arc::clone(&t);
+        run(threads, ops, move |_, i| t.metrics().set_gauge(MetricNames::KV_CACHE_SIZE_MB, i as f64))
+    };
+    let gauge = t.metrics().gauge(MetricNames::KV_CACHE_SIZE_MB);
+    let handle = run(threads, ops, move |_, i| gauge.set(i as f64));
+    report("gauge", threads, ops, baseline, handle);
+
+    let value = |t: usize, i: usize| 1.0 + ((t * 7919 + i) % 1000) as f64;
+    let baseline = {
+        let t = Arc::clone(&t);
+        run(threads, ops, move |thread, i| {
+            t.metrics().record_histogram(MetricNames::INFERENCE_LATENCY_MS, value(thread, i))
+        })
+    };
+    let histogram = t.metrics().histogram(MetricNames::INFERENCE_LATENCY_MS);
+    let handle = run(threads, ops, move |thread, i| histogram.record(value(thread, i)));
+    report("histogram", threads, ops, baseline, handle);
+
+    let recorded = t.metrics().get_histogram_stats(MetricNames::INFERENCE_LATENCY_MS).map_or(0, |s| s.count);
+    assert_eq!(recorded, 2 * threads * ops);
+}
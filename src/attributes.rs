@@ -0,0 +1,226 @@
+//! Typed attribute values for spans, events and links, and the attribute
+//! sets that identify metric series.
+
+use std::fmt;
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+/// Attribute value, mirroring the OTLP `AnyValue` scalar and array types
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+    StringArray(Vec<String>),
+    IntArray(Vec<i64>),
+    DoubleArray(Vec<f64>),
+    BoolArray(Vec<bool>),
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+            write!(f, "[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            write!(f, "]")
+        }
+        match self {
+            AttributeValue::String(v) => write!(f, "{}", v),
+            AttributeValue::Int(v) => write!(f, "{}", v),
+            AttributeValue::Double(v) => write!(f, "{}", v),
+            AttributeValue::Bool(v) => write!(f, "{}", v),
+            AttributeValue::StringArray(v) => list(f, v),
+            AttributeValue::IntArray(v) => list(f, v),
+            AttributeValue::DoubleArray(v) => list(f, v),
+            AttributeValue::BoolArray(v) => list(f, v),
+        }
+    }
+}
+
+impl PartialEq<&str> for AttributeValue {
+    fn eq(&self, other: &&str) -> bool {
+        matches!(self, AttributeValue::String(v) if v == other)
+    }
+}
+
+macro_rules! impl_from {
+    ($($from:ty => $variant:ident($conv:expr)),* $(,)?) => {
+        $(
+            impl From<$from> for AttributeValue {
+                fn from(value: $from) -> Self {
+                    AttributeValue::$variant($conv(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from! {
+    &str => String(str::to_string),
+    String => String(std::convert::identity),
+    &String => String(String::clone),
+    i64 => Int(std::convert::identity),
+    i32 => Int(i64::from),
+    u32 => Int(i64::from),
+    // Saturates rather than wrapping for values beyond i64::MAX
+    u64 => Int(|v: u64| i64::try_from(v).unwrap_or(i64::MAX)),
+    usize => Int(|v: usize| i64::try_from(v).unwrap_or(i64::MAX)),
+    f64 => Double(std::convert::identity),
+    f32 => Double(f64::from),
+    bool => Bool(std::convert::identity),
+    Vec<String> => StringArray(std::convert::identity),
+    Vec<&str> => StringArray(|v: Vec<&str>| v.into_iter().map(str::to_string).collect()),
+    Vec<i64> => IntArray(std::convert::identity),
+    Vec<f64> => DoubleArray(std::convert::identity),
+    Vec<bool> => BoolArray(std::convert::identity),
+}
+
+/// Insertion-ordered attribute map; setting an existing key replaces its value
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Attributes(Vec<(String, AttributeValue)>);
+
+impl Attributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        let key = key.into();
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&AttributeValue> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AttributeValue)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K: Into<String>, V: Into<AttributeValue>> FromIterator<(K, V)> for Attributes {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut attributes = Attributes::new();
+        for (k, v) in iter {
+            attributes.insert(k, v);
+        }
+        attributes
+    }
+}
+
+/// Attribute set identifying one metric series, such as
+/// `[("model", "bitnet-3b")]`. Pairs are kept sorted by key so the same set
+/// in any order names the same series; a repeated key keeps its last value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MetricAttributes(Vec<(String, String)>);
+
+impl MetricAttributes {
+    pub fn new(pairs: &[(&str, &str)]) -> Self {
+        let mut attributes: Vec<(String, String)> = Vec::with_capacity(pairs.len());
+        for (k, v) in pairs {
+            match attributes.iter_mut().find(|(key, _)| key == k) {
+                Some((_, existing)) => *existing = v.to_string(),
+                None => attributes.push((k.to_string(), v.to_string())),
+            }
+        }
+        attributes.sort();
+        Self(attributes)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The same pairs as typed span-style attributes, for OTLP encoding
+    pub fn to_attributes(&self) -> Attributes {
+        self.iter().collect()
+    }
+}
+
+/// Serialized as a JSON object, `{"model": "bitnet-3b"}`
+impl Serialize for MetricAttributes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in &self.0 {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_impls() {
+        assert_eq!(AttributeValue::from("bitnet"), AttributeValue::String("bitnet".into()));
+        assert_eq!(AttributeValue::from(1024usize), AttributeValue::Int(1024));
+        assert_eq!(AttributeValue::from(u64::MAX), AttributeValue::Int(i64::MAX));
+        assert_eq!(AttributeValue::from(0.8), AttributeValue::Double(0.8));
+        assert_eq!(AttributeValue::from(true), AttributeValue::Bool(true));
+        assert_eq!(AttributeValue::from(vec!["a", "b"]), AttributeValue::StringArray(vec!["a".into(), "b".into()]));
+    }
+
+    #[test]
+    fn test_insert_overwrites() {
+        let mut attrs = Attributes::new();
+        attrs.insert("model", "bitnet");
+        attrs.insert("tokens", 12);
+        attrs.insert("model", "mamba");
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs.get("model"), Some(&AttributeValue::from("mamba")));
+        assert_eq!(attrs.iter().next().unwrap().0, "model");
+    }
+
+    #[test]
+    fn test_display_and_serialize() {
+        assert_eq!(AttributeValue::IntArray(vec![1, 2]).to_string(), "[1,2]");
+        let attrs: Attributes = [("a", AttributeValue::Int(3)), ("b", AttributeValue::Bool(false))].into_iter().collect();
+        assert_eq!(serde_json::to_string(&attrs).unwrap(), r#"[["a",3],["b",false]]"#);
+    }
+
+    #[test]
+    fn test_metric_attributes_identity() {
+        let a = MetricAttributes::new(&[("tenant", "acme"), ("model", "bitnet-3b")]);
+        let b = MetricAttributes::new(&[("model", "mamba"), ("tenant", "acme"), ("model", "bitnet-3b")]);
+        assert_eq!(a, b);
+        assert_eq!(a.iter().next(), Some(("model", "bitnet-3b")));
+        assert_eq!(a.get("tenant"), Some("acme"));
+        assert_eq!(serde_json::to_string(&a).unwrap(), r#"{"model":"bitnet-3b","tenant":"acme"}"#);
+        assert_eq!(a.to_attributes().get("model"), Some(&AttributeValue::from("bitnet-3b")));
+    }
+}
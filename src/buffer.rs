@@ -0,0 +1,228 @@
+//! Bounded storage for finished spans and log records awaiting export.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::config::OverflowPolicy;
+use crate::logs::LogRecord;
+use crate::SpanRecord;
+
+/// Fixed-capacity FIFO of items awaiting export.
+///
+/// When full, new items are handled according to the [`OverflowPolicy`], and
+/// every item discarded is counted. Once `flush_threshold` items are waiting,
+/// `ready` is notified so a batch processor can drain the queue.
+pub(crate) struct BoundedQueue<T> {
+    items: Mutex<VecDeque<T>>,
+    space: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+    flush_threshold: usize,
+    dropped: AtomicU64,
+    pub(crate) ready: tokio::sync::Notify,
+}
+
+/// Longest [`OverflowPolicy::Block`] waits for space before dropping the
+/// item, outside a tokio runtime
+pub(crate) const MAX_BLOCK: Duration = Duration::from_millis(100);
+
+pub(crate) type SpanQueue = BoundedQueue<SpanRecord>;
+pub(crate) type LogQueue = BoundedQueue<LogRecord>;
+
+impl<T> BoundedQueue<T> {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy, flush_threshold: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            items: Mutex::new(VecDeque::with_capacity(capacity.min(4096))),
+            space: Condvar::new(),
+            capacity,
+            policy,
+            flush_threshold: flush_threshold.clamp(1, capacity),
+            dropped: AtomicU64::new(0),
+            ready: tokio::sync::Notify::new(),
+        }
+    }
+
+    pub(crate) fn push(&self, item: T) {
+        let mut items = self.items.lock().unwrap();
+        if items.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    items.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                OverflowPolicy::Block => {
+                    self.ready.notify_one();
+                    // Waiting on a runtime thread would park a worker, or on a
+                    // current-thread runtime the processor itself
+                    if tokio::runtime::Handle::try_current().is_ok() {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    let (guard, timeout) = self
+                        .space
+                        .wait_timeout_while(items, MAX_BLOCK, |i| i.len() >= self.capacity)
+                        .unwrap();
+                    if timeout.timed_out() {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    items = guard;
+                }
+            }
+        }
+        items.push_back(item);
+        if items.len() >= self.flush_threshold {
+            self.ready.notify_one();
+        }
+    }
+
+    /// Remove up to `max` items, oldest first
+    pub(crate) fn drain(&self, max: usize) -> Vec<T> {
+        let mut items = self.items.lock().unwrap();
+        let n = max.min(items.len());
+        let drained: Vec<T> = items.drain(..n).collect();
+        if !drained.is_empty() {
+            self.space.notify_all();
+        }
+        drained
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+impl<T: Clone> BoundedQueue<T> {
+    pub(crate) fn to_vec(&self) -> Vec<T> {
+        self.items.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+    use crate::{SigmaTelemetry, SpanOperation};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn fill(queue: &SpanQueue, names: &[&str]) {
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        for name in names {
+            t.start_span(name, SpanOperation::Inference).set_ok();
+        }
+        for span in t.drain_spans(usize::MAX) {
+            queue.push(span);
+        }
+    }
+
+    fn names(queue: &SpanQueue) -> Vec<String> {
+        queue.to_vec().into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let queue = SpanQueue::new(2, OverflowPolicy::DropOldest, 2);
+        fill(&queue, &["a", "b", "c"]);
+        assert_eq!(names(&queue), ["b", "c"]);
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let queue = SpanQueue::new(2, OverflowPolicy::DropNewest, 2);
+        fill(&queue, &["a", "b", "c"]);
+        assert_eq!(names(&queue), ["a", "b"]);
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[test]
+    fn test_block_waits_for_drain() {
+        let queue = Arc::new(SpanQueue::new(1, OverflowPolicy::Block, 1));
+        fill(&queue, &["a"]);
+
+        let producer = {
+            let queue = Arc::clone(&queue);
+            std::thread::spawn(move || fill(&queue, &["b"]))
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(names(&queue), ["a"]);
+
+        assert_eq!(queue.drain(1).len(), 1);
+        producer.join().unwrap();
+        assert_eq!(names(&queue), ["b"]);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn test_block_gives_up_after_max_block() {
+        let queue = SpanQueue::new(1, OverflowPolicy::Block, 1);
+        let start = std::time::Instant::now();
+        fill(&queue, &["a", "b"]);
+        assert!(start.elapsed() >= MAX_BLOCK);
+        assert_eq!(names(&queue), ["a"]);
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_block_on_current_thread_runtime_drops() {
+        use crate::exporter::{ExportFormat, Exporter};
+        use crate::processor::BatchSpanProcessor;
+
+        let config = TelemetryConfig {
+            max_queue_size: 1,
+            overflow_policy: OverflowPolicy::Block,
+            ..TelemetryConfig::default()
+        };
+        let t = Arc::new(SigmaTelemetry::new(config.clone()));
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Json));
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            t.start_span("step", SpanOperation::TokenGeneration).set_ok();
+        }
+        assert!(start.elapsed() < MAX_BLOCK, "recording must not wait for a processor it starves");
+        assert_eq!(t.snapshot().dropped_spans, 2);
+        processor.force_flush().await.unwrap();
+        assert_eq!(t.metrics().get_counter("spans.exported"), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_block_on_runtime_worker_drops() {
+        let queue = Arc::new(SpanQueue::new(1, OverflowPolicy::Block, 1));
+        // Nothing drains the queue, so a blocking push would wait MAX_BLOCK
+        let recorder = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move {
+                let start = std::time::Instant::now();
+                fill(&queue, &["a", "b", "c", "d"]);
+                start.elapsed()
+            })
+        };
+        let elapsed = recorder.await.unwrap();
+        assert!(elapsed < MAX_BLOCK, "worker stalled for {elapsed:?}");
+        assert_eq!(names(&queue), ["a"]);
+        assert_eq!(queue.dropped(), 3);
+
+        // Outside the runtime Block still waits for space
+        let blocking = std::thread::spawn(move || {
+            let start = std::time::Instant::now();
+            fill(&queue, &["e"]);
+            (start.elapsed(), queue.dropped())
+        });
+        let (elapsed, dropped) = blocking.join().unwrap();
+        assert!(elapsed >= MAX_BLOCK);
+        assert_eq!(dropped, 4);
+    }
+}
@@ -1,14 +1,25 @@
 use serde::{Deserialize, Serialize};
 
+use crate::histogram::HistogramConfig;
+use crate::tail_sampling::TailSamplingConfig;
+use crate::temporality::Temporality;
+
 /// Telemetry configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelemetryConfig {
     /// Service name for span attribution
     pub service_name: String,
-    /// OTLP endpoint for span export
+    /// OTLP/gRPC collector endpoint, used by `ExportFormat::OtlpGrpc`
     pub otlp_endpoint: String,
+    /// OTLP/HTTP collector endpoint; `/v1/traces`, `/v1/metrics` and
+    /// `/v1/logs` are appended
+    #[serde(default = "default_otlp_http_endpoint")]
+    pub otlp_http_endpoint: String,
     /// Sampling rate (0.0 to 1.0)
     pub sampling_rate: f64,
+    /// Head sampler applied to root spans and remote parents
+    #[serde(default)]
+    pub sampler: SamplerKind,
     /// Enable metrics collection
     pub metrics_enabled: bool,
     /// Enable trace export
@@ -19,6 +30,60 @@ pub struct TelemetryConfig {
     pub export_interval_secs: u64,
     /// Maximum spans to buffer before flush
     pub max_buffer_size: usize,
+    /// Capacity of the finished-span queue
+    #[serde(default = "default_max_queue_size")]
+    pub max_queue_size: usize,
+    /// What to do with new spans when the queue is full
+    #[serde(default)]
+    pub overflow_policy: OverflowPolicy,
+    /// Buffer finished traces and keep only those matching a policy
+    #[serde(default)]
+    pub tail_sampling: Option<TailSamplingConfig>,
+    /// Histogram aggregation, by default and per metric
+    #[serde(default)]
+    pub histograms: HistogramConfig,
+    /// Temporality of sums and histograms sent by OTLP exporters, unless
+    /// overridden with `Exporter::with_metrics_temporality`. JSON, stdout
+    /// and Prometheus output is cumulative.
+    #[serde(default)]
+    pub metrics_temporality: Temporality,
+}
+
+/// Built-in head samplers, see [`crate::sampling`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    AlwaysOn,
+    AlwaysOff,
+    /// Sample `sampling_rate` of traces by trace ID
+    TraceIdRatio,
+    /// Follow a remote parent's decision, otherwise sample `sampling_rate` of traces
+    #[default]
+    ParentBasedTraceIdRatio,
+}
+
+/// Handling of finished spans when the span queue is at capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Evict the oldest queued span to make room
+    #[default]
+    DropOldest,
+    /// Discard the span being recorded
+    DropNewest,
+    /// Wait for the batch processor to drain the queue, for at most 100 ms,
+    /// then discard the span being recorded. Only threads outside a tokio
+    /// runtime wait; on a runtime thread waiting would stall the worker, so
+    /// the span is discarded at once, as with `DropNewest`.
+    Block,
+}
+
+fn default_otlp_http_endpoint() -> String {
+    "http://localhost:4318".to_string()
+}
+
+fn default_max_queue_size() -> usize {
+    2048
 }
 
 impl Default for TelemetryConfig {
@@ -26,12 +91,19 @@ impl Default for TelemetryConfig {
         Self {
             service_name: "ryzanstein".to_string(),
             otlp_endpoint: "http://localhost:4317".to_string(),
+            otlp_http_endpoint: default_otlp_http_endpoint(),
             sampling_rate: 1.0,
+            sampler: SamplerKind::default(),
             metrics_enabled: true,
             traces_enabled: true,
             ryzanstein_url: "http://localhost:8000".to_string(),
             export_interval_secs: 10,
             max_buffer_size: 1024,
+            max_queue_size: default_max_queue_size(),
+            overflow_policy: OverflowPolicy::default(),
+            tail_sampling: None,
+            histograms: HistogramConfig::default(),
+            metrics_temporality: Temporality::default(),
         }
     }
 }
@@ -0,0 +1,305 @@
+//! Trace context: span identifiers, trace flags and trace state.
+
+use std::fmt;
+
+use crate::error::TelemetryError;
+
+/// 128-bit W3C trace identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TraceId([u8; 16]);
+
+/// 64-bit W3C span identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SpanId([u8; 8]);
+
+impl TraceId {
+    /// The all-zero trace ID, which W3C Trace Context treats as invalid
+    pub const INVALID: TraceId = TraceId([0; 16]);
+
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0
+    }
+
+    /// Parse a 32-character lowercase hex trace ID
+    pub fn from_hex(hex: &str) -> Result<Self, TelemetryError> {
+        let mut bytes = [0u8; 16];
+        decode_hex(hex, &mut bytes)?;
+        Ok(Self(bytes))
+    }
+
+    pub fn is_valid(&self) -> bool {
+        *self != Self::INVALID
+    }
+
+    /// Generate a random, valid trace ID
+    pub fn random() -> Self {
+        loop {
+            let id = Self(rand::random());
+            if id.is_valid() {
+                return id;
+            }
+        }
+    }
+}
+
+impl SpanId {
+    /// The all-zero span ID, which W3C Trace Context treats as invalid
+    pub const INVALID: SpanId = SpanId([0; 8]);
+
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0
+    }
+
+    /// Parse a 16-character lowercase hex span ID
+    pub fn from_hex(hex: &str) -> Result<Self, TelemetryError> {
+        let mut bytes = [0u8; 8];
+        decode_hex(hex, &mut bytes)?;
+        Ok(Self(bytes))
+    }
+
+    pub fn is_valid(&self) -> bool {
+        *self != Self::INVALID
+    }
+
+    /// Generate a random, valid span ID
+    pub fn random() -> Self {
+        loop {
+            let id = Self(rand::random());
+            if id.is_valid() {
+                return id;
+            }
+        }
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+impl fmt::Display for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+/// W3C trace flags (the last field of `traceparent`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TraceFlags(u8);
+
+impl TraceFlags {
+    pub const NOT_SAMPLED: TraceFlags = TraceFlags(0x00);
+    pub const SAMPLED: TraceFlags = TraceFlags(0x01);
+
+    pub fn new(flags: u8) -> Self {
+        Self(flags)
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.0 & Self::SAMPLED.0 != 0
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+}
+
+/// Maximum number of `tracestate` list members allowed by W3C Trace Context
+const MAX_TRACE_STATE_ENTRIES: usize = 32;
+
+/// Vendor-specific `tracestate` entries, most recently updated first
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TraceState(Vec<(String, String)>);
+
+impl TraceState {
+    /// Parse a `tracestate` header value, rejecting malformed lists
+    pub fn from_header(header: &str) -> Result<Self, TelemetryError> {
+        let mut entries: Vec<(String, String)> = Vec::new();
+        for member in header.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            let (key, value) = member
+                .split_once('=')
+                .ok_or_else(|| TelemetryError::SpanError(format!("invalid tracestate member: {}", member)))?;
+            if !valid_trace_state_key(key) || !valid_trace_state_value(value) {
+                return Err(TelemetryError::SpanError(format!("invalid tracestate member: {}", member)));
+            }
+            if entries.iter().any(|(k, _)| k == key) {
+                return Err(TelemetryError::SpanError(format!("duplicate tracestate key: {}", key)));
+            }
+            entries.push((key.to_string(), value.to_string()));
+        }
+        if entries.len() > MAX_TRACE_STATE_ENTRIES {
+            return Err(TelemetryError::SpanError(format!(
+                "tracestate has {} members, limit is {}",
+                entries.len(),
+                MAX_TRACE_STATE_ENTRIES
+            )));
+        }
+        Ok(Self(entries))
+    }
+
+    /// Render as a `tracestate` header value
+    pub fn header(&self) -> String {
+        self.0
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Insert or update an entry, moving it to the front as the spec requires
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), TelemetryError> {
+        if !valid_trace_state_key(key) || !valid_trace_state_value(value) {
+            return Err(TelemetryError::SpanError(format!("invalid tracestate entry: {}={}", key, value)));
+        }
+        self.0.retain(|(k, _)| k != key);
+        self.0.insert(0, (key.to_string(), value.to_string()));
+        self.0.truncate(MAX_TRACE_STATE_ENTRIES);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn valid_trace_state_key(key: &str) -> bool {
+    let simple = |k: &str| {
+        k.bytes().next().is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            && k.bytes()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-*/".contains(&c))
+    };
+    match key.split_once('@') {
+        Some((tenant, system)) => {
+            !tenant.is_empty() && tenant.len() <= 241 && system.len() <= 14 && simple(tenant) && simple(system)
+        }
+        None => key.len() <= 256 && simple(key),
+    }
+}
+
+fn valid_trace_state_value(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 256
+        && !value.ends_with(' ')
+        && value.bytes().all(|c| (0x20..=0x7e).contains(&c) && c != b',' && c != b'=')
+}
+
+/// Identity of a span within its trace, plus the state that travels with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub trace_flags: TraceFlags,
+    pub trace_state: TraceState,
+    /// Set when the context was extracted from another process
+    pub is_remote: bool,
+}
+
+impl SpanContext {
+    /// A local, sampled context with empty trace state
+    pub fn new(trace_id: TraceId, span_id: SpanId) -> Self {
+        Self {
+            trace_id,
+            span_id,
+            trace_flags: TraceFlags::SAMPLED,
+            trace_state: TraceState::default(),
+            is_remote: false,
+        }
+    }
+
+    /// Both IDs are non-zero
+    pub fn is_valid(&self) -> bool {
+        self.trace_id.is_valid() && self.span_id.is_valid()
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    for b in bytes {
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+fn decode_hex(hex: &str, out: &mut [u8]) -> Result<(), TelemetryError> {
+    if hex.len() != out.len() * 2 {
+        return Err(TelemetryError::SpanError(format!(
+            "expected {} hex characters, got {}",
+            out.len() * 2,
+            hex.len()
+        )));
+    }
+    if !hex.bytes().all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c)) {
+        return Err(TelemetryError::SpanError(format!("invalid hex id: {}", hex)));
+    }
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| TelemetryError::SpanError(format!("invalid hex id: {}", hex)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_id_hex_roundtrip() {
+        let id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        assert_eq!(id.to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(id.to_bytes()[0], 0x4b);
+    }
+
+    #[test]
+    fn test_span_id_hex_roundtrip() {
+        let id = SpanId::from_hex("00f067aa0ba902b7").unwrap();
+        assert_eq!(id.to_string(), "00f067aa0ba902b7");
+    }
+
+    #[test]
+    fn test_invalid_hex_rejected() {
+        assert!(SpanId::from_hex("00f067aa0ba902").is_err());
+        assert!(SpanId::from_hex("00F067AA0BA902B7").is_err());
+        assert!(TraceId::from_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_random_ids_valid_and_distinct() {
+        let a = TraceId::random();
+        let b = TraceId::random();
+        assert!(a.is_valid());
+        assert_ne!(a, b);
+        assert!(SpanId::random().is_valid());
+        assert!(!SpanContext::new(TraceId::INVALID, SpanId::random()).is_valid());
+    }
+
+    #[test]
+    fn test_trace_state_parse_and_render() {
+        let state = TraceState::from_header("rojo=00f067aa0ba902b7, congo=t61rcWkgMzE").unwrap();
+        assert_eq!(state.get("congo"), Some("t61rcWkgMzE"));
+        assert_eq!(state.header(), "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE");
+        assert!(TraceState::from_header("Rojo=1").is_err());
+        assert!(TraceState::from_header("rojo=1,rojo=2").is_err());
+        assert!(TraceState::from_header("novalue").is_err());
+    }
+
+    #[test]
+    fn test_trace_state_insert_moves_to_front() {
+        let mut state = TraceState::from_header("a=1,b=2").unwrap();
+        state.insert("b", "3").unwrap();
+        assert_eq!(state.header(), "b=3,a=1");
+        assert!(state.insert("tenant@sys", "x").is_ok());
+        assert!(state.insert("bad key", "x").is_err());
+    }
+}
@@ -2,13 +2,25 @@
 
 use crate::config::TelemetryConfig;
 use crate::error::TelemetryError;
-use crate::SpanRecord;
+use crate::logs::LogRecord;
+use crate::{
+    AttributeValue, Attributes, MetricReader, MetricsCollector, MetricsData, Series, SpanEvent, SpanKind, SpanLink,
+    SpanRecord, SpanStatus, Temporality,
+};
 use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Export format
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExportFormat {
     Otlp,
+    /// Protobuf over gRPC to the collector's `TraceService`
+    #[cfg(feature = "jaeger")]
+    OtlpGrpc,
+    /// Binary protobuf (`application/x-protobuf`) over HTTP to `/v1/traces`
+    /// under `otlp_http_endpoint`, like `Otlp`
+    #[cfg(feature = "protobuf")]
+    OtlpHttpProtobuf,
     Json,
     Stdout,
 }
@@ -17,6 +29,12 @@ pub enum ExportFormat {
 pub struct Exporter {
     config: TelemetryConfig,
     format: ExportFormat,
+    /// Collects metrics in this exporter's temporality, with its own delta
+    /// checkpoint
+    metrics_reader: MetricReader,
+    /// Created on the first gRPC export and reused, with its connection
+    #[cfg(feature = "jaeger")]
+    grpc: std::sync::Mutex<Option<std::sync::Arc<crate::otlp_grpc::BlockingGrpcExporter>>>,
 }
 
 /// Exported span in wire format
@@ -25,9 +43,119 @@ pub struct ExportedSpan {
     pub name: String,
     pub service: String,
     pub operation: String,
+    pub kind: SpanKind,
+    pub trace_id: String,
+    pub span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub trace_state: String,
     pub duration_ms: Option<f64>,
     pub status: String,
-    pub attributes: Vec<(String, String)>,
+    pub attributes: Attributes,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<ExportedEvent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<ExportedLink>,
+}
+
+/// Exported span event in wire format
+#[derive(Debug, Serialize)]
+pub struct ExportedEvent {
+    pub name: String,
+    pub time_unix_nano: u64,
+    pub attributes: Attributes,
+}
+
+impl From<&SpanEvent> for ExportedEvent {
+    fn from(event: &SpanEvent) -> Self {
+        ExportedEvent {
+            name: event.name.clone(),
+            time_unix_nano: unix_nanos(event.timestamp),
+            attributes: event.attributes.clone(),
+        }
+    }
+}
+
+/// Exported span link in wire format
+#[derive(Debug, Serialize)]
+pub struct ExportedLink {
+    pub trace_id: String,
+    pub span_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub trace_state: String,
+    pub attributes: Attributes,
+}
+
+impl From<&SpanLink> for ExportedLink {
+    fn from(link: &SpanLink) -> Self {
+        ExportedLink {
+            trace_id: link.trace_id.to_string(),
+            span_id: link.span_id.to_string(),
+            trace_state: link.trace_state.header(),
+            attributes: link.attributes.clone(),
+        }
+    }
+}
+
+/// Exported log record in wire format, one per line in JSON lines output
+#[derive(Debug, Serialize)]
+pub struct ExportedLog {
+    pub time_unix_nano: u64,
+    pub severity: String,
+    pub severity_number: i32,
+    pub body: String,
+    pub service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_id: Option<String>,
+    #[serde(skip_serializing_if = "Attributes::is_empty")]
+    pub attributes: Attributes,
+}
+
+pub(crate) fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+}
+
+fn otlp_attributes(attributes: &Attributes) -> Vec<serde_json::Value> {
+    attributes
+        .iter()
+        .map(|(k, v)| {
+            serde_json::json!({
+                "key": k,
+                "value": otlp_value(v)
+            })
+        })
+        .collect()
+}
+
+/// OTLP/JSON `AnyValue`; int64 is a decimal string per the proto3 JSON mapping
+fn otlp_value(value: &AttributeValue) -> serde_json::Value {
+    fn array(values: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({ "arrayValue": { "values": values } })
+    }
+    match value {
+        AttributeValue::String(v) => serde_json::json!({ "stringValue": v }),
+        AttributeValue::Int(v) => serde_json::json!({ "intValue": v.to_string() }),
+        AttributeValue::Double(v) => serde_json::json!({ "doubleValue": otlp_double(*v) }),
+        AttributeValue::Bool(v) => serde_json::json!({ "boolValue": v }),
+        AttributeValue::StringArray(v) => array(v.iter().map(|s| serde_json::json!({ "stringValue": s })).collect()),
+        AttributeValue::IntArray(v) => array(v.iter().map(|i| serde_json::json!({ "intValue": i.to_string() })).collect()),
+        AttributeValue::DoubleArray(v) => array(v.iter().map(|d| serde_json::json!({ "doubleValue": otlp_double(*d) })).collect()),
+        AttributeValue::BoolArray(v) => array(v.iter().map(|b| serde_json::json!({ "boolValue": b })).collect()),
+    }
+}
+
+/// JSON has no NaN or infinity literals; proto3 JSON spells them as strings
+fn otlp_double(v: f64) -> serde_json::Value {
+    if v.is_nan() {
+        serde_json::json!("NaN")
+    } else if v.is_infinite() {
+        serde_json::json!(if v > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        serde_json::json!(v)
+    }
 }
 
 impl From<&SpanRecord> for ExportedSpan {
@@ -36,114 +164,491 @@ impl From<&SpanRecord> for ExportedSpan {
             name: record.name.clone(),
             service: record.service.clone(),
             operation: record.operation.to_string(),
+            kind: record.kind,
+            trace_id: record.trace_id.to_string(),
+            span_id: record.span_id.to_string(),
+            parent_span_id: record.parent_span_id.map(|id| id.to_string()),
+            trace_state: record.trace_state.header(),
             duration_ms: record.duration.map(|d| d.as_secs_f64() * 1000.0),
             status: match &record.status {
-                crate::SpanStatus::Ok => "ok".to_string(),
-                crate::SpanStatus::Error(msg) => format!("error: {}", msg),
-                crate::SpanStatus::Unset => "unset".to_string(),
+                SpanStatus::Ok => "ok".to_string(),
+                SpanStatus::Error(msg) => format!("error: {}", msg),
+                SpanStatus::Unset => "unset".to_string(),
             },
             attributes: record.attributes.clone(),
+            events: record.events.iter().map(ExportedEvent::from).collect(),
+            links: record.links.iter().map(ExportedLink::from).collect(),
         }
     }
 }
 
 impl Exporter {
-    /// Create a new exporter
+    /// Create a new exporter. OTLP formats collect metrics in
+    /// `metrics_temporality` from the config; JSON and stdout output is
+    /// cumulative.
     pub fn new(config: TelemetryConfig, format: ExportFormat) -> Self {
-        Self { config, format }
+        let temporality = match format {
+            ExportFormat::Json | ExportFormat::Stdout => Temporality::Cumulative,
+            _ => config.metrics_temporality,
+        };
+        Self {
+            config,
+            format,
+            metrics_reader: MetricReader::new(temporality),
+            #[cfg(feature = "jaeger")]
+            grpc: std::sync::Mutex::new(None),
+        }
     }
 
-    /// Export spans
+    #[cfg(feature = "jaeger")]
+    fn grpc(&self) -> Result<std::sync::Arc<crate::otlp_grpc::BlockingGrpcExporter>, TelemetryError> {
+        let mut slot = self.grpc.lock().unwrap();
+        if let Some(grpc) = slot.as_ref() {
+            return Ok(std::sync::Arc::clone(grpc));
+        }
+        let grpc = std::sync::Arc::new(crate::otlp_grpc::BlockingGrpcExporter::new(&self.config)?);
+        *slot = Some(std::sync::Arc::clone(&grpc));
+        Ok(grpc)
+    }
+
+    /// Export spans; nothing is sent when `traces_enabled` is off
     pub fn export(&self, spans: &[SpanRecord]) -> Result<String, TelemetryError> {
+        if !self.config.traces_enabled {
+            return Ok(format!("Traces disabled, skipped {} spans", spans.len()));
+        }
         let exported: Vec<ExportedSpan> = spans.iter().map(|s| s.into()).collect();
 
         match self.format {
             ExportFormat::Json | ExportFormat::Stdout => serde_json::to_string_pretty(&exported)
                 .map_err(|e| TelemetryError::ExportError(e.to_string())),
+            #[cfg(feature = "jaeger")]
+            ExportFormat::OtlpGrpc => {
+                let accepted = self.grpc()?.export(spans)?;
+                Ok(format!(
+                    "Exported {} spans to {}",
+                    accepted, self.config.otlp_endpoint
+                ))
+            }
+            #[cfg(feature = "protobuf")]
+            ExportFormat::OtlpHttpProtobuf => {
+                use prost::Message;
+
+                let body = crate::otlp_proto::trace_request(&self.config.service_name, spans).encode_to_vec();
+                self.post_otlp("/v1/traces", "application/x-protobuf", body)
+                    .map(|endpoint| format!("Exported {} spans to {}", spans.len(), endpoint))
+            }
             ExportFormat::Otlp => {
-                let resource_spans = serde_json::json!({
-                    "resourceSpans": [{
-                        "resource": {
-                            "attributes": [{
-                                "key": "service.name",
-                                "value": { "stringValue": &self.config.service_name }
-                            }]
-                        },
-                        "scopeSpans": [{
-                            "scope": {
-                                "name": "sigma-telemetry",
-                                "version": env!("CARGO_PKG_VERSION")
-                            },
-                            "spans": exported.iter().map(|s| {
-                                serde_json::json!({
-                                    "name": &s.name,
-                                    "kind": 1,
-                                    "attributes": s.attributes.iter().map(|(k, v)| {
-                                        serde_json::json!({
-                                            "key": k,
-                                            "value": { "stringValue": v }
-                                        })
-                                    }).collect::<Vec<_>>(),
-                                    "status": {
-                                        "code": if s.status.starts_with("error") { 2 } else { 1 },
-                                        "message": &s.status
-                                    },
-                                    "durationNanos": s.duration_ms.map(|ms| (ms * 1_000_000.0) as u64).unwrap_or(0),
-                                })
-                            }).collect::<Vec<_>>()
-                        }]
-                    }]
-                });
-
-                let body = serde_json::to_string(&resource_spans)
+                let resource_spans = self.otlp_trace_request(spans);
+
+                let body = serde_json::to_vec(&resource_spans)
                     .map_err(|e| TelemetryError::ExportError(e.to_string()))?;
+                self.post_otlp("/v1/traces", "application/json", body)
+                    .map(|endpoint| format!("Exported {} spans to {}", spans.len(), endpoint))
+            }
+        }
+    }
 
-                let client = reqwest::blocking::Client::builder()
-                    .timeout(std::time::Duration::from_secs(10))
-                    .build()
+    /// Collect metrics in `temporality` instead of the format's default
+    pub fn with_metrics_temporality(mut self, temporality: Temporality) -> Self {
+        self.metrics_reader = MetricReader::new(temporality);
+        self
+    }
+
+    /// Temporality this exporter collects metrics in
+    pub fn metrics_temporality(&self) -> Temporality {
+        self.metrics_reader.temporality()
+    }
+
+    /// Snapshot of `metrics` in this exporter's temporality. Delta
+    /// snapshots cover the interval since this exporter's previous one,
+    /// whatever other exporters collect.
+    pub fn collect_metrics(&self, metrics: &MetricsCollector) -> MetricsData {
+        metrics.collect(&self.metrics_reader)
+    }
+
+    /// Export a metrics snapshot: counters as monotonic Sums,
+    /// up-down counters as non-monotonic Sums, gauges as Gauges and
+    /// histograms as explicit-bucket or exponential Histograms
+    pub fn export_metrics(&self, data: &MetricsData) -> Result<String, TelemetryError> {
+        let series = data.series();
+        match self.format {
+            ExportFormat::Json | ExportFormat::Stdout => {
+                serde_json::to_string_pretty(data).map_err(|e| TelemetryError::ExportError(e.to_string()))
+            }
+            #[cfg(feature = "jaeger")]
+            ExportFormat::OtlpGrpc => {
+                self.grpc()?.export_metrics(data)?;
+                Ok(format!("Exported {} metric data points to {}", series, self.config.otlp_endpoint))
+            }
+            #[cfg(feature = "protobuf")]
+            ExportFormat::OtlpHttpProtobuf => {
+                use prost::Message;
+
+                let body = crate::otlp_proto::metrics_request(&self.config.service_name, data).encode_to_vec();
+                self.post_otlp("/v1/metrics", "application/x-protobuf", body)
+                    .map(|endpoint| format!("Exported {} metric data points to {}", series, endpoint))
+            }
+            ExportFormat::Otlp => {
+                let body = serde_json::to_vec(&self.otlp_metrics_request(data))
                     .map_err(|e| TelemetryError::ExportError(e.to_string()))?;
+                self.post_otlp("/v1/metrics", "application/json", body)
+                    .map(|endpoint| format!("Exported {} metric data points to {}", series, endpoint))
+            }
+        }
+    }
 
-                let endpoint = format!("{}/v1/traces", self.config.otlp_endpoint);
-                let response = client
-                    .post(&endpoint)
-                    .header("Content-Type", "application/json")
-                    .body(body)
-                    .send();
-
-                match response {
-                    Ok(resp) if resp.status().is_success() => Ok(format!(
-                        "Exported {} spans to {}",
-                        spans.len(),
-                        endpoint
-                    )),
-                    Ok(resp) => Err(TelemetryError::ExportError(format!(
-                        "OTLP endpoint returned {}: {}",
-                        resp.status(),
-                        resp.text().unwrap_or_default()
-                    ))),
-                    Err(e) => Err(TelemetryError::ExportError(format!(
-                        "Failed to reach OTLP endpoint {}: {}",
-                        endpoint, e
-                    ))),
+    /// Export log records: JSON lines for `Json` and `Stdout`, otherwise
+    /// OTLP logs to `/v1/logs`
+    pub fn export_logs(&self, logs: &[LogRecord]) -> Result<String, TelemetryError> {
+        match self.format {
+            ExportFormat::Json | ExportFormat::Stdout => {
+                let mut lines = String::new();
+                for record in logs {
+                    let exported = ExportedLog {
+                        time_unix_nano: unix_nanos(record.timestamp),
+                        severity: record.severity.to_string(),
+                        severity_number: record.severity.number(),
+                        body: record.body.clone(),
+                        service: self.config.service_name.clone(),
+                        trace_id: record.trace_id.map(|id| id.to_string()),
+                        span_id: record.span_id.map(|id| id.to_string()),
+                        attributes: record.attributes.clone(),
+                    };
+                    let line = serde_json::to_string(&exported).map_err(|e| TelemetryError::ExportError(e.to_string()))?;
+                    lines.push_str(&line);
+                    lines.push('\n');
                 }
+                Ok(lines)
+            }
+            #[cfg(feature = "jaeger")]
+            ExportFormat::OtlpGrpc => {
+                let accepted = self.grpc()?.export_logs(logs)?;
+                Ok(format!("Exported {} logs to {}", accepted, self.config.otlp_endpoint))
+            }
+            #[cfg(feature = "protobuf")]
+            ExportFormat::OtlpHttpProtobuf => {
+                use prost::Message;
+
+                let body = crate::otlp_proto::logs_request(&self.config.service_name, logs).encode_to_vec();
+                self.post_otlp("/v1/logs", "application/x-protobuf", body)
+                    .map(|endpoint| format!("Exported {} logs to {}", logs.len(), endpoint))
+            }
+            ExportFormat::Otlp => {
+                let body = serde_json::to_vec(&self.otlp_logs_request(logs))
+                    .map_err(|e| TelemetryError::ExportError(e.to_string()))?;
+                self.post_otlp("/v1/logs", "application/json", body)
+                    .map(|endpoint| format!("Exported {} logs to {}", logs.len(), endpoint))
             }
         }
     }
+
+    /// URL of OTLP/HTTP signal `path`, e.g. `/v1/traces`
+    fn otlp_http_url(&self, path: &str) -> String {
+        format!("{}{}", self.config.otlp_http_endpoint.trim_end_matches('/'), path)
+    }
+
+    /// POST an OTLP/HTTP payload to `path` under `otlp_http_endpoint`,
+    /// returning the full endpoint URL on success
+    fn post_otlp(&self, path: &str, content_type: &str, body: Vec<u8>) -> Result<String, TelemetryError> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| TelemetryError::ExportError(e.to_string()))?;
+
+        let endpoint = self.otlp_http_url(path);
+        let response = client
+            .post(&endpoint)
+            .header("Content-Type", content_type)
+            .body(body)
+            .send();
+
+        match response {
+            Ok(resp) if resp.status().is_success() => Ok(endpoint),
+            Ok(resp) => Err(TelemetryError::ExportError(format!(
+                "OTLP endpoint returned {}: {}",
+                resp.status(),
+                resp.text().unwrap_or_default()
+            ))),
+            Err(e) => Err(TelemetryError::ExportError(format!(
+                "Failed to reach OTLP endpoint {}: {}",
+                endpoint, e
+            ))),
+        }
+    }
+
+    /// OTLP/JSON `ExportTraceServiceRequest` body, following the proto3 JSON
+    /// mapping: lowerCamelCase fields, hex IDs, fixed64 timestamps as decimal
+    /// strings, and default-valued fields omitted
+    fn otlp_trace_request(&self, spans: &[SpanRecord]) -> serde_json::Value {
+        serde_json::json!({
+            "resourceSpans": [{
+                "resource": self.otlp_resource(),
+                "scopeSpans": [{
+                    "scope": otlp_scope(),
+                    "spans": spans.iter().map(otlp_span).collect::<Vec<_>>()
+                }]
+            }]
+        })
+    }
+
+    /// OTLP/JSON `ExportMetricsServiceRequest` body, under the same resource
+    /// and scope as spans. Series sharing a name become data points of one
+    /// metric.
+    fn otlp_metrics_request(&self, data: &MetricsData) -> serde_json::Value {
+        let start = data.start_time_unix_nano.to_string();
+        let time = data.time_unix_nano.to_string();
+        let temporality = otlp_temporality(data.temporality);
+        let counters = metric_groups(&data.counters, |series| {
+            serde_json::json!({
+                "sum": {
+                    "dataPoints": series.iter().map(|s| serde_json::json!({
+                        "attributes": otlp_attributes(&s.attributes.to_attributes()),
+                        "startTimeUnixNano": &start,
+                        "timeUnixNano": &time,
+                        "asInt": s.value.to_string(),
+                    })).collect::<Vec<_>>(),
+                    "aggregationTemporality": temporality,
+                    "isMonotonic": true,
+                }
+            })
+        });
+        let gauges = metric_groups(&data.gauges, |series| {
+            serde_json::json!({
+                "gauge": {
+                    "dataPoints": series.iter().map(|s| serde_json::json!({
+                        "attributes": otlp_attributes(&s.attributes.to_attributes()),
+                        "timeUnixNano": &time,
+                        "asDouble": otlp_double(s.value),
+                    })).collect::<Vec<_>>(),
+                }
+            })
+        });
+        let up_down_counters = metric_groups(&data.up_down_counters, |series| {
+            serde_json::json!({
+                "sum": {
+                    "dataPoints": series.iter().map(|s| serde_json::json!({
+                        "attributes": otlp_attributes(&s.attributes.to_attributes()),
+                        "startTimeUnixNano": &start,
+                        "timeUnixNano": &time,
+                        "asInt": s.value.to_string(),
+                    })).collect::<Vec<_>>(),
+                    "aggregationTemporality": temporality,
+                    "isMonotonic": false,
+                }
+            })
+        });
+        let histograms = metric_groups(&data.histograms, |series| {
+            serde_json::json!({
+                "histogram": {
+                    "dataPoints": series.iter().map(|s| {
+                        let h = &s.value;
+                        serde_json::json!({
+                            "attributes": otlp_attributes(&s.attributes.to_attributes()),
+                            "startTimeUnixNano": &start,
+                            "timeUnixNano": &time,
+                            "count": h.count.to_string(),
+                            "sum": otlp_double(h.sum),
+                            "bucketCounts": h.bucket_counts.iter().map(u64::to_string).collect::<Vec<_>>(),
+                            "explicitBounds": &h.bounds,
+                            "min": otlp_double(h.min),
+                            "max": otlp_double(h.max),
+                        })
+                    }).collect::<Vec<_>>(),
+                    "aggregationTemporality": temporality,
+                }
+            })
+        });
+        let exponential_histograms = metric_groups(&data.exponential_histograms, |series| {
+            let buckets = |b: &crate::ExponentialBuckets| {
+                serde_json::json!({
+                    "offset": b.offset,
+                    "bucketCounts": b.bucket_counts.iter().map(u64::to_string).collect::<Vec<_>>(),
+                })
+            };
+            serde_json::json!({
+                "exponentialHistogram": {
+                    "dataPoints": series.iter().map(|s| {
+                        let h = &s.value;
+                        serde_json::json!({
+                            "attributes": otlp_attributes(&s.attributes.to_attributes()),
+                            "startTimeUnixNano": &start,
+                            "timeUnixNano": &time,
+                            "count": h.count.to_string(),
+                            "sum": otlp_double(h.sum),
+                            "scale": h.scale,
+                            "zeroCount": h.zero_count.to_string(),
+                            "positive": buckets(&h.positive),
+                            "negative": buckets(&h.negative),
+                            "min": otlp_double(h.min),
+                            "max": otlp_double(h.max),
+                        })
+                    }).collect::<Vec<_>>(),
+                    "aggregationTemporality": temporality,
+                }
+            })
+        });
+        let metrics = counters
+            .chain(up_down_counters)
+            .chain(gauges)
+            .chain(histograms)
+            .chain(exponential_histograms);
+        serde_json::json!({
+            "resourceMetrics": [{
+                "resource": self.otlp_resource(),
+                "scopeMetrics": [{
+                    "scope": otlp_scope(),
+                    "metrics": metrics.collect::<Vec<_>>()
+                }]
+            }]
+        })
+    }
+
+    /// OTLP/JSON `ExportLogsServiceRequest` body
+    fn otlp_logs_request(&self, logs: &[LogRecord]) -> serde_json::Value {
+        serde_json::json!({
+            "resourceLogs": [{
+                "resource": self.otlp_resource(),
+                "scopeLogs": [{
+                    "scope": otlp_scope(),
+                    "logRecords": logs.iter().map(otlp_log).collect::<Vec<_>>()
+                }]
+            }]
+        })
+    }
+
+    fn otlp_resource(&self) -> serde_json::Value {
+        serde_json::json!({
+            "attributes": [{
+                "key": "service.name",
+                "value": { "stringValue": &self.config.service_name }
+            }]
+        })
+    }
+}
+
+/// `AggregationTemporality` enum number
+fn otlp_temporality(temporality: Temporality) -> i32 {
+    match temporality {
+        Temporality::Delta => 1,
+        Temporality::Cumulative => 2,
+    }
+}
+
+/// One OTLP metric per run of same-named series, with `data` supplying the
+/// type-specific body around their data points
+fn metric_groups<'a, T>(
+    series: &'a [Series<T>],
+    data: impl Fn(&[Series<T>]) -> serde_json::Value + 'a,
+) -> impl Iterator<Item = serde_json::Value> + 'a {
+    series.chunk_by(|a, b| a.name == b.name).map(move |group| {
+        let mut metric = data(group);
+        metric["name"] = serde_json::json!(group[0].name);
+        metric
+    })
+}
+
+fn otlp_scope() -> serde_json::Value {
+    serde_json::json!({
+        "name": "sigma-telemetry",
+        "version": env!("CARGO_PKG_VERSION")
+    })
+}
+
+/// `Span.SpanKind` enum number; 0 (unspecified) is never emitted
+fn otlp_span_kind(kind: SpanKind) -> i32 {
+    match kind {
+        SpanKind::Internal => 1,
+        SpanKind::Server => 2,
+        SpanKind::Client => 3,
+        SpanKind::Producer => 4,
+        SpanKind::Consumer => 5,
+    }
+}
+
+fn otlp_log(record: &LogRecord) -> serde_json::Value {
+    let time = unix_nanos(record.timestamp).to_string();
+    let mut log = serde_json::json!({
+        "timeUnixNano": &time,
+        "observedTimeUnixNano": &time,
+        "severityNumber": record.severity.number(),
+        "severityText": record.severity.as_str(),
+        "body": { "stringValue": &record.body },
+        "attributes": otlp_attributes(&record.attributes),
+    });
+    if let (Some(trace_id), Some(span_id)) = (record.trace_id, record.span_id) {
+        log["traceId"] = serde_json::json!(trace_id.to_string());
+        log["spanId"] = serde_json::json!(span_id.to_string());
+        log["flags"] = serde_json::json!(record.trace_flags.to_u8());
+    }
+    log
+}
+
+fn otlp_span(record: &SpanRecord) -> serde_json::Value {
+    let start = unix_nanos(record.start_time);
+    let end = start + record.duration.map_or(0, |d| d.as_nanos() as u64);
+    let mut span = serde_json::json!({
+        "traceId": record.trace_id.to_string(),
+        "spanId": record.span_id.to_string(),
+        "name": &record.name,
+        "kind": otlp_span_kind(record.kind),
+        "startTimeUnixNano": start.to_string(),
+        "endTimeUnixNano": end.to_string(),
+        "attributes": otlp_attributes(&record.attributes),
+        "events": record.events.iter().map(|e| {
+            serde_json::json!({
+                "timeUnixNano": unix_nanos(e.timestamp).to_string(),
+                "name": &e.name,
+                "attributes": otlp_attributes(&e.attributes),
+            })
+        }).collect::<Vec<_>>(),
+        "links": record.links.iter().map(|l| {
+            let mut link = serde_json::json!({
+                "traceId": l.trace_id.to_string(),
+                "spanId": l.span_id.to_string(),
+                "attributes": otlp_attributes(&l.attributes),
+            });
+            if !l.trace_state.is_empty() {
+                link["traceState"] = serde_json::json!(l.trace_state.header());
+            }
+            link
+        }).collect::<Vec<_>>(),
+        "status": match &record.status {
+            SpanStatus::Unset => serde_json::json!({}),
+            SpanStatus::Ok => serde_json::json!({ "code": 1 }),
+            SpanStatus::Error(msg) => serde_json::json!({ "code": 2, "message": msg }),
+        },
+    });
+    if let Some(parent) = record.parent_span_id {
+        span["parentSpanId"] = serde_json::json!(parent.to_string());
+    }
+    if !record.trace_state.is_empty() {
+        span["traceState"] = serde_json::json!(record.trace_state.header());
+    }
+    span
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{SpanOperation, SpanStatus};
+    use crate::test_support::HttpCollector;
+    use crate::{MetricAttributes, SpanId, SpanOperation, TraceId, TraceState};
 
     fn sample_span() -> SpanRecord {
         SpanRecord {
             name: "test".to_string(),
             service: "ryzanstein".to_string(),
             operation: SpanOperation::Inference,
+            kind: SpanKind::Internal,
+            trace_id: TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            span_id: SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            parent_span_id: None,
+            trace_state: TraceState::default(),
+            parent_is_remote: false,
             start_time: std::time::SystemTime::now(),
             duration: Some(std::time::Duration::from_millis(42)),
-            attributes: vec![("model".to_string(), "bitnet".to_string())],
+            attributes: [("model", "bitnet")].into_iter().collect(),
+            events: Vec::new(),
+            links: Vec::new(),
             status: SpanStatus::Ok,
         }
     }
@@ -156,6 +661,310 @@ mod tests {
         assert!(result.contains("bitnet"));
     }
 
+    #[test]
+    fn test_json_export_carries_ids() {
+        let mut child = sample_span();
+        child.span_id = SpanId::from_hex("a3ce929d0e0e4736").unwrap();
+        child.parent_span_id = Some(sample_span().span_id);
+
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Json);
+        let result = exporter.export(&[sample_span(), child]).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed[0]["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(parsed[0].get("parent_span_id").is_none());
+        assert_eq!(parsed[1]["span_id"], "a3ce929d0e0e4736");
+        assert_eq!(parsed[1]["parent_span_id"], "00f067aa0ba902b7");
+    }
+
+    #[test]
+    fn test_events_exported() {
+        let mut span = sample_span();
+        span.events.push(SpanEvent {
+            name: "first_token".to_string(),
+            timestamp: UNIX_EPOCH + std::time::Duration::from_nanos(1_700_000_000_000_000_123),
+            attributes: [("token.index", 0)].into_iter().collect(),
+        });
+
+        let json = Exporter::new(TelemetryConfig::default(), ExportFormat::Json)
+            .export(std::slice::from_ref(&span))
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["events"][0]["name"], "first_token");
+        assert_eq!(parsed[0]["events"][0]["time_unix_nano"], 1_700_000_000_000_000_123u64);
+
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let otlp = exporter.otlp_trace_request(std::slice::from_ref(&span));
+        let event = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["events"][0];
+        assert_eq!(event["timeUnixNano"], "1700000000000000123");
+        assert_eq!(event["attributes"][0]["key"], "token.index");
+    }
+
+    #[test]
+    fn test_otlp_typed_attribute_encoding() {
+        let mut span = sample_span();
+        span.attributes.insert("tokens", 128);
+        span.attributes.insert("temperature", 0.7);
+        span.attributes.insert("streaming", true);
+        span.attributes.insert("stop", vec!["</s>", "\n"]);
+        span.attributes.insert("score", f64::NAN);
+
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let otlp = exporter.otlp_trace_request(std::slice::from_ref(&span));
+        let attrs = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["attributes"];
+        assert_eq!(attrs[0]["value"]["stringValue"], "bitnet");
+        assert_eq!(attrs[1]["value"]["intValue"], "128");
+        assert_eq!(attrs[2]["value"]["doubleValue"], 0.7);
+        assert_eq!(attrs[3]["value"]["boolValue"], true);
+        assert_eq!(attrs[4]["value"]["arrayValue"]["values"][1]["stringValue"], "\n");
+        assert_eq!(attrs[5]["value"]["doubleValue"], "NaN");
+    }
+
+    #[test]
+    fn test_links_exported() {
+        let mut span = sample_span();
+        span.links.push(SpanLink {
+            trace_id: TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            span_id: SpanId::from_hex("b7ad6b7169203331").unwrap(),
+            trace_state: TraceState::from_header("rojo=00f067aa0ba902b7").unwrap(),
+            attributes: [("batch.slot", 2)].into_iter().collect(),
+        });
+        span.trace_state = TraceState::from_header("gateway=edge1").unwrap();
+
+        let json = Exporter::new(TelemetryConfig::default(), ExportFormat::Json)
+            .export(std::slice::from_ref(&span))
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["links"][0]["trace_id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(parsed[0]["trace_state"], "gateway=edge1");
+        assert_eq!(parsed[0]["links"][0]["trace_state"], "rojo=00f067aa0ba902b7");
+
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let otlp = exporter.otlp_trace_request(std::slice::from_ref(&span));
+        let link = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["links"][0];
+        assert_eq!(link["spanId"], "b7ad6b7169203331");
+        assert_eq!(link["traceState"], "rojo=00f067aa0ba902b7");
+        assert_eq!(otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["traceState"], "gateway=edge1");
+        assert_eq!(link["attributes"][0]["value"]["intValue"], "2");
+    }
+
+    fn collector_config(collector: &HttpCollector) -> TelemetryConfig {
+        TelemetryConfig {
+            otlp_http_endpoint: collector.endpoint.clone(),
+            ..TelemetryConfig::default()
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn test_otlp_http_protobuf_export() {
+        use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+        use prost::Message;
+
+        let collector = HttpCollector::start();
+        let config = TelemetryConfig {
+            // Only the gRPC transport uses this
+            otlp_endpoint: "http://127.0.0.1:1".to_string(),
+            ..collector_config(&collector)
+        };
+        let result = Exporter::new(config, ExportFormat::OtlpHttpProtobuf)
+            .export(&[sample_span()])
+            .unwrap();
+        assert_eq!(result, format!("Exported 1 spans to {}/v1/traces", collector.endpoint));
+
+        let captured = &collector.requests_to("/v1/traces")[0];
+        assert_eq!(captured.content_type, "application/x-protobuf");
+        let request = ExportTraceServiceRequest::decode(captured.body.as_slice()).unwrap();
+        let span = &request.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.name, "test");
+        assert_eq!(span.trace_id, sample_span().trace_id.to_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_otlp_json_export_posts_to_traces() {
+        let collector = HttpCollector::start();
+        Exporter::new(collector_config(&collector), ExportFormat::Otlp)
+            .export(&[sample_span()])
+            .unwrap();
+
+        let captured = &collector.requests_to("/v1/traces")[0];
+        assert_eq!(captured.content_type, "application/json");
+        assert_eq!(captured.json()["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["name"], "test");
+    }
+
+    fn sample_metrics() -> MetricsData {
+        MetricsData {
+            start_time_unix_nano: 1_700_000_000_000_000_000,
+            time_unix_nano: 1_700_000_010_000_000_000,
+            temporality: Temporality::Cumulative,
+            counters: vec![
+                Series::new("ryzanstein.inference.requests", MetricAttributes::new(&[("model", "bitnet-3b")]), 40),
+                Series::new("ryzanstein.inference.requests", MetricAttributes::new(&[("model", "mamba-2.8b")]), 2),
+            ],
+            gauges: vec![Series::new("ryzanstein.system.gpu_utilization", MetricAttributes::default(), 85.5)],
+            up_down_counters: vec![Series::new("ryzanstein.inference.active_sequences", MetricAttributes::default(), -1)],
+            histograms: vec![Series::new(
+                "ryzanstein.inference.latency_ms",
+                MetricAttributes::default(),
+                crate::HistogramData {
+                    count: 3,
+                    sum: 36.0,
+                    min: 4.0,
+                    max: 20.0,
+                    bounds: vec![5.0, 10.0],
+                    bucket_counts: vec![1, 1, 1],
+                },
+            )],
+            exponential_histograms: vec![Series::new(
+                "ryzanstein.model.load_ms",
+                MetricAttributes::default(),
+                crate::ExponentialHistogramData {
+                    count: 4,
+                    sum: 62_999.5,
+                    min: -0.5,
+                    max: 60_000.0,
+                    scale: -1,
+                    zero_count: 1,
+                    positive: crate::ExponentialBuckets {
+                        offset: 5,
+                        bucket_counts: vec![1, 0, 1],
+                    },
+                    negative: crate::ExponentialBuckets {
+                        offset: -1,
+                        bucket_counts: vec![1],
+                    },
+                },
+            )],
+        }
+    }
+
+    #[test]
+    fn test_otlp_json_golden_metrics() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        assert_golden("otlp_metrics_request", &exporter.otlp_metrics_request(&sample_metrics()));
+    }
+
+    #[test]
+    fn test_otlp_metrics_share_resource_and_scope() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let traces = exporter.otlp_trace_request(&[sample_span()]);
+        let metrics = exporter.otlp_metrics_request(&sample_metrics());
+        assert_eq!(metrics["resourceMetrics"][0]["resource"], traces["resourceSpans"][0]["resource"]);
+        assert_eq!(metrics["resourceMetrics"][0]["scopeMetrics"][0]["scope"], traces["resourceSpans"][0]["scopeSpans"][0]["scope"]);
+    }
+
+    #[test]
+    fn test_otlp_metrics_group_series_by_name() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let request = exporter.otlp_metrics_request(&sample_metrics());
+        let metrics = &request["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics.as_array().unwrap().len(), 5);
+        assert_eq!(metrics[1]["sum"]["isMonotonic"], false);
+        assert_eq!(metrics[1]["sum"]["dataPoints"][0]["asInt"], "-1");
+        let points = &metrics[0]["sum"]["dataPoints"];
+        assert_eq!(points[0]["attributes"][0]["key"], "model");
+        assert_eq!(points[0]["attributes"][0]["value"]["stringValue"], "bitnet-3b");
+        assert_eq!(points[1]["asInt"], "2");
+    }
+
+    #[test]
+    fn test_otlp_metrics_temporality() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let request = exporter.otlp_metrics_request(&MetricsData {
+            temporality: Temporality::Delta,
+            ..sample_metrics()
+        });
+        let metrics = &request["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics[0]["sum"]["aggregationTemporality"], 1);
+        assert_eq!(metrics[3]["histogram"]["aggregationTemporality"], 1);
+        assert_eq!(metrics[2]["gauge"].get("aggregationTemporality"), None);
+    }
+
+    #[test]
+    fn test_metrics_temporality_per_exporter() {
+        let config = TelemetryConfig {
+            metrics_temporality: Temporality::Delta,
+            ..TelemetryConfig::default()
+        };
+        let otlp = Exporter::new(config.clone(), ExportFormat::Otlp);
+        let json = Exporter::new(config.clone(), ExportFormat::Json);
+        let second = Exporter::new(config.clone(), ExportFormat::Otlp);
+        let overridden = Exporter::new(config, ExportFormat::Otlp).with_metrics_temporality(Temporality::Cumulative);
+        assert_eq!(otlp.metrics_temporality(), Temporality::Delta);
+        assert_eq!(json.metrics_temporality(), Temporality::Cumulative);
+        assert_eq!(overridden.metrics_temporality(), Temporality::Cumulative);
+
+        let telemetry = crate::SigmaTelemetry::new(TelemetryConfig::default());
+        let metrics = telemetry.metrics();
+        metrics.increment_by("requests", 3);
+        assert_eq!(otlp.collect_metrics(metrics).counters[0].value, 3);
+        metrics.increment_by("requests", 2);
+        assert_eq!(second.collect_metrics(metrics).counters[0].value, 5);
+        assert_eq!(otlp.collect_metrics(metrics).counters[0].value, 2, "exporters keep separate checkpoints");
+        assert_eq!(json.collect_metrics(metrics).counters[0].value, 5);
+    }
+
+    #[test]
+    fn test_otlp_metrics_posts_to_metrics() {
+        let collector = HttpCollector::start();
+        let result = Exporter::new(collector_config(&collector), ExportFormat::Otlp)
+            .export_metrics(&sample_metrics())
+            .unwrap();
+        assert!(result.contains("Exported 6 metric data points"), "{result}");
+
+        let body = collector.requests_to("/v1/metrics")[0].json();
+        let metrics = &body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics[0]["sum"]["isMonotonic"], true);
+        assert_eq!(metrics[0]["sum"]["dataPoints"][0]["asInt"], "40");
+    }
+
+    fn sample_logs() -> Vec<LogRecord> {
+        let at = UNIX_EPOCH + std::time::Duration::from_nanos(1_700_000_000_001_200_000);
+        let span = sample_span();
+        let context = crate::SpanContext::new(span.trace_id, span.span_id);
+        vec![
+            LogRecord {
+                timestamp: at,
+                ..LogRecord::new(crate::Severity::Warn, "logit overflow")
+                    .with_attribute("token.index", 17)
+                    .with_context(&context)
+            },
+            LogRecord {
+                timestamp: at,
+                ..LogRecord::new(crate::Severity::Info, "model warm")
+            },
+        ]
+    }
+
+    #[test]
+    fn test_otlp_json_golden_logs() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        assert_golden("otlp_logs_request", &exporter.otlp_logs_request(&sample_logs()));
+    }
+
+    #[test]
+    fn test_logs_json_lines() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Json);
+        let output = exporter.export_logs(&sample_logs()).unwrap();
+        let lines: Vec<serde_json::Value> = output.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["severity"], "WARN");
+        assert_eq!(lines[0]["span_id"], "00f067aa0ba902b7");
+        assert_eq!(lines[0]["attributes"], serde_json::json!([["token.index", 17]]));
+        assert!(lines[1].get("trace_id").is_none());
+    }
+
+    #[test]
+    fn test_otlp_logs_posts_to_logs() {
+        let collector = HttpCollector::start();
+        Exporter::new(collector_config(&collector), ExportFormat::Otlp)
+            .export_logs(&sample_logs())
+            .unwrap();
+        let body = collector.requests_to("/v1/logs")[0].json();
+        let records = &body["resourceLogs"][0]["scopeLogs"][0]["logRecords"];
+        assert_eq!(records[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(records[0]["severityNumber"], 13);
+    }
+
     #[test]
     fn test_otlp_export_formats_correctly() {
         // OTLP export will fail to connect in test env, but we can verify it
@@ -168,19 +977,217 @@ mod tests {
                 let err_msg = format!("{}", e);
                 // Should mention the OTLP endpoint in the error
                 assert!(
-                    err_msg.contains("localhost:4317") || err_msg.contains("OTLP"),
+                    err_msg.contains("localhost:4318") || err_msg.contains("OTLP"),
                     "Unexpected error: {err_msg}"
                 );
             }
         }
     }
 
+    #[test]
+    fn test_default_http_exports_use_port_4318() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        for path in ["/v1/traces", "/v1/metrics", "/v1/logs"] {
+            assert_eq!(exporter.otlp_http_url(path), format!("http://localhost:4318{path}"));
+        }
+        assert_eq!(TelemetryConfig::default().otlp_endpoint, "http://localhost:4317", "gRPC keeps 4317");
+    }
+
+    /// Check a value against the OTLP/JSON rules golden files could otherwise
+    /// freeze in wrongly: lowerCamelCase keys, lowercase hex IDs, 64-bit
+    /// integers as decimal strings, enums as numbers and non-finite doubles
+    /// as their proto3 JSON names
+    fn check_otlp_json(path: &str, value: &serde_json::Value) {
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        let is_decimal = |v: &serde_json::Value| {
+            v.as_str().is_some_and(|s| s.parse::<u64>().is_ok() || s.parse::<i64>().is_ok())
+        };
+        let is_double = |v: &serde_json::Value| v.is_number() || matches!(v.as_str(), Some("NaN" | "Infinity" | "-Infinity"));
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, v) in map {
+                    let at = format!("{path}.{key}");
+                    let camel_case = key.starts_with(|c: char| c.is_ascii_lowercase()) && !key.contains('_');
+                    assert!(camel_case, "{at}: not lowerCamelCase");
+                    match key.as_str() {
+                        "traceId" => assert!(v.as_str().is_some_and(|s| is_hex(s, 32)), "{at}: {v}"),
+                        "spanId" | "parentSpanId" => assert!(v.as_str().is_some_and(|s| is_hex(s, 16)), "{at}: {v}"),
+                        "traceState" => assert!(v.as_str().is_some_and(|s| s.contains('=')), "{at}: {v}"),
+                        k if k.ends_with("UnixNano") => assert!(is_decimal(v), "{at}: {v}"),
+                        "intValue" | "asInt" | "count" | "zeroCount" => assert!(is_decimal(v), "{at}: {v}"),
+                        "bucketCounts" => assert!(v.as_array().unwrap().iter().all(is_decimal), "{at}: {v}"),
+                        "kind" => assert!(v.as_u64().is_some_and(|n| n <= 5), "{at}: {v}"),
+                        "code" => assert!(v.as_u64().is_some_and(|n| n <= 2), "{at}: {v}"),
+                        "aggregationTemporality" => {
+                            assert!(v.as_u64().is_some_and(|n| (1..=2).contains(&n)), "{at}: {v}")
+                        }
+                        "severityNumber" => assert!(v.as_u64().is_some_and(|n| (1..=24).contains(&n)), "{at}: {v}"),
+                        "flags" | "scale" | "offset" => assert!(v.is_i64(), "{at}: {v}"),
+                        "doubleValue" | "asDouble" | "min" | "max" => assert!(is_double(v), "{at}: {v}"),
+                        // `sum` is also the Sum metric body
+                        "sum" if !v.is_object() => assert!(is_double(v), "{at}: {v}"),
+                        _ => check_otlp_json(&at, v),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    check_otlp_json(&format!("{path}[{i}]"), item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_golden_files_follow_otlp_json_mapping() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let golden: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            check_otlp_json(&path.file_name().unwrap().to_string_lossy(), &golden);
+        }
+    }
+
+    #[test]
+    fn test_otlp_json_check_rejects_bad_encoding() {
+        for bad in [
+            serde_json::json!({ "traceId": "4BF92F3577B34DA6A3CE929D0E0E4736" }),
+            serde_json::json!({ "startTimeUnixNano": 1_700_000_000_000_000_000u64 }),
+            serde_json::json!({ "kind": "SPAN_KIND_SERVER" }),
+            serde_json::json!({ "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736" }),
+        ] {
+            assert!(std::panic::catch_unwind(|| check_otlp_json("bad", &bad)).is_err(), "{bad}");
+        }
+    }
+
+    /// Compare `actual` with `tests/golden/<name>.json`; set `UPDATE_GOLDEN=1`
+    /// to rewrite the file instead
+    fn assert_golden(name: &str, actual: &serde_json::Value) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{name}.json"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, serde_json::to_string_pretty(actual).unwrap() + "\n").unwrap();
+            return;
+        }
+        let expected: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            actual,
+            &expected,
+            "{} differs:\n{}",
+            path.display(),
+            serde_json::to_string_pretty(actual).unwrap()
+        );
+    }
+
+    /// Root, child and linked spans with fixed IDs and timestamps
+    fn golden_trace() -> Vec<SpanRecord> {
+        let at = |nanos: u64| UNIX_EPOCH + std::time::Duration::from_nanos(nanos);
+        let mut root = sample_span();
+        root.start_time = at(1_700_000_000_000_000_000);
+        root.duration = Some(std::time::Duration::from_millis(42));
+        root.attributes.insert("tokens", 128);
+        root.attributes.insert("temperature", 0.7);
+        root.attributes.insert("streaming", true);
+        root.attributes.insert("stop", vec!["</s>"]);
+
+        let mut child = sample_span();
+        child.name = "decode".to_string();
+        child.operation = SpanOperation::TokenGeneration;
+        child.span_id = SpanId::from_hex("a3ce929d0e0e4736").unwrap();
+        child.parent_span_id = Some(root.span_id);
+        child.start_time = at(1_700_000_000_001_000_000);
+        child.duration = Some(std::time::Duration::from_micros(1500));
+        child.attributes = Attributes::new();
+        child.events.push(SpanEvent {
+            name: "first_token".to_string(),
+            timestamp: at(1_700_000_000_001_200_000),
+            attributes: [("token.index", 0)].into_iter().collect(),
+        });
+        child.status = SpanStatus::Error("kv cache exhausted".to_string());
+
+        let mut linked = sample_span();
+        linked.name = "batch".to_string();
+        linked.trace_id = TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap();
+        linked.span_id = SpanId::from_hex("b7ad6b7169203331").unwrap();
+        linked.start_time = at(1_700_000_000_002_000_000);
+        linked.duration = None;
+        linked.attributes = Attributes::new();
+        linked.trace_state = TraceState::from_header("gateway=edge1").unwrap();
+        linked.links.push(SpanLink {
+            trace_id: root.trace_id,
+            span_id: root.span_id,
+            trace_state: TraceState::from_header("rojo=00f067aa0ba902b7").unwrap(),
+            attributes: [("batch.slot", 2)].into_iter().collect(),
+        });
+        linked.status = SpanStatus::Unset;
+        linked.kind = SpanKind::Consumer;
+
+        vec![root, child, linked]
+    }
+
+    #[test]
+    fn test_otlp_json_golden_trace() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        assert_golden("otlp_trace_request", &exporter.otlp_trace_request(&golden_trace()));
+    }
+
+    #[test]
+    fn test_otlp_json_golden_special_values() {
+        let mut span = golden_trace().remove(0);
+        span.attributes = [
+            ("nan", AttributeValue::Double(f64::NAN)),
+            ("inf", AttributeValue::Double(f64::NEG_INFINITY)),
+            ("big", AttributeValue::from(u64::MAX)),
+            ("ids", AttributeValue::IntArray(vec![-1, 0, 1])),
+            ("unicode", AttributeValue::from("Σ \"q\" \u{1F600}")),
+        ]
+        .into_iter()
+        .collect();
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        assert_golden("otlp_special_values", &exporter.otlp_trace_request(&[span]));
+    }
+
+    #[test]
+    fn test_otlp_status_codes() {
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let otlp = exporter.otlp_trace_request(&golden_trace());
+        let spans = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["status"]["code"], 1);
+        assert_eq!(spans[1]["status"]["code"], 2);
+        assert_eq!(spans[1]["status"]["message"], "kv cache exhausted");
+        assert!(spans[2]["status"].get("code").is_none(), "unset status is code 0, the default");
+        assert_eq!(spans[0]["endTimeUnixNano"], "1700000000042000000");
+        assert_eq!(spans[2]["startTimeUnixNano"], spans[2]["endTimeUnixNano"]);
+        assert!(spans[0].get("durationNanos").is_none());
+        assert!(spans[0].get("parentSpanId").is_none());
+    }
+
+    #[test]
+    fn test_otlp_span_kinds() {
+        let kinds = [SpanKind::Internal, SpanKind::Server, SpanKind::Client, SpanKind::Producer, SpanKind::Consumer];
+        let spans: Vec<SpanRecord> = kinds
+            .iter()
+            .map(|&kind| SpanRecord { kind, ..sample_span() })
+            .collect();
+        let exporter = Exporter::new(TelemetryConfig::default(), ExportFormat::Otlp);
+        let otlp = exporter.otlp_trace_request(&spans);
+        let encoded: Vec<_> = (0..kinds.len())
+            .map(|i| otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][i]["kind"].as_i64().unwrap())
+            .collect();
+        assert_eq!(encoded, [1, 2, 3, 4, 5]);
+    }
+
     #[test]
     fn test_exported_span_conversion() {
         let span = sample_span();
         let exported = ExportedSpan::from(&span);
         assert_eq!(exported.operation, "inference");
         assert_eq!(exported.status, "ok");
+        assert_eq!(serde_json::to_value(&exported).unwrap()["kind"], "internal");
         assert!(exported.duration_ms.unwrap() > 0.0);
     }
 }
@@ -0,0 +1,36 @@
+//! Process-wide telemetry handle.
+
+use std::sync::{Arc, RwLock};
+
+use crate::{OwnedSpanGuard, SigmaTelemetry, SpanOperation};
+
+static GLOBAL_TELEMETRY: RwLock<Option<Arc<SigmaTelemetry>>> = RwLock::new(None);
+
+/// Install the global telemetry instance, returning the previous one
+pub fn set_telemetry(telemetry: Arc<SigmaTelemetry>) -> Option<Arc<SigmaTelemetry>> {
+    GLOBAL_TELEMETRY.write().unwrap().replace(telemetry)
+}
+
+/// The global telemetry instance, if one has been installed
+pub fn telemetry() -> Option<Arc<SigmaTelemetry>> {
+    GLOBAL_TELEMETRY.read().unwrap().clone()
+}
+
+/// Start an owned span on the global instance; `None` if none is installed
+pub fn start_span(name: &str, operation: SpanOperation) -> Option<OwnedSpanGuard> {
+    telemetry().map(|t| t.start_owned_span(name, operation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+
+    #[test]
+    fn test_global_span() {
+        let t = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+        set_telemetry(Arc::clone(&t));
+        start_span("agent", SpanOperation::AgentExecute).unwrap().set_ok();
+        assert_eq!(t.snapshot().span_count, 1);
+    }
+}
@@ -0,0 +1,1019 @@
+//! Bounded-memory histogram aggregations.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ExponentialBuckets, ExponentialHistogramData, HistogramData, HistogramStats, DEFAULT_HISTOGRAM_BOUNDS};
+
+/// How a histogram stores recorded values. Every aggregation records in O(1)
+/// and uses memory bounded by its configuration, not by the sample count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistogramAggregation {
+    /// Counts per explicit bucket; quantiles are interpolated within the
+    /// bucket that holds them, so accuracy depends on the bounds
+    ExplicitBuckets { bounds: Vec<f64> },
+    /// DDSketch: quantiles within `relative_accuracy` of the true value
+    /// (0.01 = 1%), using at most `max_buckets` buckets per sign. When the
+    /// limit is hit the lowest buckets are merged, degrading only low
+    /// quantiles.
+    Sketch { relative_accuracy: f64, max_buckets: usize },
+    /// OpenTelemetry base-2 exponential buckets, exported as an OTLP
+    /// ExponentialHistogram. Starts at `max_scale` (at most 20) and halves
+    /// the resolution whenever more than `max_size` buckets per sign would be
+    /// needed, so any range of values fits.
+    Exponential { max_size: usize, max_scale: i32 },
+}
+
+impl HistogramAggregation {
+    /// Exponential buckets with the OpenTelemetry SDK defaults: 160 buckets,
+    /// starting at scale 20
+    pub fn exponential() -> Self {
+        HistogramAggregation::Exponential {
+            max_size: 160,
+            max_scale: MAX_SCALE,
+        }
+    }
+}
+
+impl Default for HistogramAggregation {
+    fn default() -> Self {
+        HistogramAggregation::Sketch {
+            relative_accuracy: 0.01,
+            max_buckets: 2048,
+        }
+    }
+}
+
+/// Histogram aggregation settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistogramConfig {
+    /// Aggregation for histograms without an override
+    #[serde(default)]
+    pub default: HistogramAggregation,
+    /// Per-metric aggregation, keyed by metric name
+    #[serde(default)]
+    pub overrides: HashMap<String, HistogramAggregation>,
+    /// Shards per histogram series, each holding a full copy of the
+    /// aggregation. Defaults to the available parallelism, at most 8; 1
+    /// suits many series recorded from few threads.
+    #[serde(default)]
+    pub shards: Option<usize>,
+}
+
+impl HistogramConfig {
+    pub(crate) fn aggregation_for(&self, name: &str) -> &HistogramAggregation {
+        self.overrides.get(name).unwrap_or(&self.default)
+    }
+
+    pub(crate) fn shard_count(&self) -> usize {
+        static DEFAULT: OnceLock<usize> = OnceLock::new();
+        self.shards.unwrap_or_else(|| {
+            *DEFAULT.get_or_init(|| std::thread::available_parallelism().map_or(4, |n| n.get()).min(MAX_DEFAULT_SHARDS))
+        })
+        .max(1)
+    }
+}
+
+/// Cap on the default [`HistogramConfig::shards`]
+const MAX_DEFAULT_SHARDS: usize = 8;
+
+/// Running state of one histogram
+#[derive(Debug, Clone)]
+pub(crate) struct Histogram {
+    count: u64,
+    sum: f64,
+    /// Running mean and sum of squared deviations (Welford), for the
+    /// standard deviation without a second pass
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+    /// Extremes since each delta reader's last
+    /// [`ShardedHistogram::merged_interval`], by reader
+    intervals: Vec<Extremes>,
+    storage: Storage,
+}
+
+/// Extremes recorded since a delta reader's previous collection
+#[derive(Debug, Clone)]
+struct Extremes {
+    reader: u64,
+    min: f64,
+    max: f64,
+}
+
+impl Extremes {
+    fn new(reader: u64) -> Self {
+        Self {
+            reader,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Buckets { bounds: Vec<f64>, counts: Vec<u64> },
+    Sketch(DdSketch),
+    Exponential(Exponential),
+}
+
+impl Histogram {
+    pub(crate) fn new(aggregation: &HistogramAggregation) -> Self {
+        let storage = match aggregation {
+            HistogramAggregation::ExplicitBuckets { bounds } => {
+                let mut bounds: Vec<f64> = bounds.iter().copied().filter(|b| b.is_finite()).collect();
+                bounds.sort_by(f64::total_cmp);
+                bounds.dedup();
+                Storage::Buckets {
+                    counts: vec![0; bounds.len() + 1],
+                    bounds,
+                }
+            }
+            HistogramAggregation::Sketch {
+                relative_accuracy,
+                max_buckets,
+            } => Storage::Sketch(DdSketch::new(*relative_accuracy, *max_buckets)),
+            HistogramAggregation::Exponential { max_size, max_scale } => {
+                Storage::Exponential(Exponential::new(*max_size, *max_scale))
+            }
+        };
+        Self {
+            count: 0,
+            sum: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            intervals: Vec::new(),
+            storage,
+        }
+    }
+
+    /// Record one value; NaN and infinities are ignored
+    pub(crate) fn record(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.count += 1;
+        self.sum += value;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        for interval in &mut self.intervals {
+            interval.min = interval.min.min(value);
+            interval.max = interval.max.max(value);
+        }
+        match &mut self.storage {
+            Storage::Buckets { bounds, counts } => counts[bounds.partition_point(|&b| b < value)] += 1,
+            Storage::Sketch(sketch) => sketch.add(value),
+            Storage::Exponential(exponential) => exponential.add(value),
+        }
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Fold in `other`, which must use the same aggregation, as if its
+    /// values had been recorded here
+    pub(crate) fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        // Chan et al.'s pairwise update of the Welford moments
+        let delta = other.mean - self.mean;
+        self.m2 += other.m2 + delta * delta * (self.count as f64 * other.count as f64 / count as f64);
+        self.mean += delta * other.count as f64 / count as f64;
+        self.count = count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        match (&mut self.storage, &other.storage) {
+            (Storage::Buckets { counts, .. }, Storage::Buckets { counts: other, .. }) => {
+                for (c, o) in counts.iter_mut().zip(other) {
+                    *c += o;
+                }
+            }
+            (Storage::Sketch(sketch), Storage::Sketch(other)) => sketch.merge(other),
+            (Storage::Exponential(exponential), Storage::Exponential(other)) => exponential.merge(other),
+            _ => unreachable!("merged histograms share one aggregation"),
+        }
+    }
+
+    /// Estimated value at quantile `q` (0.0..=1.0), `None` when empty
+    pub(crate) fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let q = q.clamp(0.0, 1.0);
+        if q == 0.0 {
+            return Some(self.min);
+        }
+        if q == 1.0 {
+            return Some(self.max);
+        }
+        let estimate = match &self.storage {
+            Storage::Buckets { bounds, counts } => bucket_quantile(bounds, counts, self.count, q, self.min, self.max),
+            Storage::Sketch(sketch) => sketch.quantile(q),
+            Storage::Exponential(exponential) => exponential.quantile(q),
+        };
+        Some(estimate.clamp(self.min, self.max))
+    }
+
+    pub(crate) fn stats(&self) -> Option<HistogramStats> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(HistogramStats {
+            count: self.count as usize,
+            sum: self.sum,
+            mean: self.sum / self.count as f64,
+            min: self.min,
+            max: self.max,
+            stddev: (self.m2 / self.count as f64).sqrt(),
+            p50: self.quantile(0.5)?,
+            p90: self.quantile(0.9)?,
+            p95: self.quantile(0.95)?,
+            p99: self.quantile(0.99)?,
+            p999: self.quantile(0.999)?,
+        })
+    }
+
+    /// Exponential form for export, `None` unless the aggregation is
+    /// [`HistogramAggregation::Exponential`]
+    pub(crate) fn to_exponential_data(&self) -> Option<ExponentialHistogramData> {
+        let Storage::Exponential(exponential) = &self.storage else {
+            return None;
+        };
+        Some(ExponentialHistogramData {
+            count: self.count,
+            sum: self.sum,
+            min: self.min,
+            max: self.max,
+            scale: exponential.scale,
+            zero_count: exponential.zero_count,
+            positive: exponential.positive.to_data(),
+            negative: exponential.negative.to_data(),
+        })
+    }
+
+    /// Explicit-bucket form for export. Sketches are re-bucketed onto
+    /// [`DEFAULT_HISTOGRAM_BOUNDS`] by each sketch bucket's representative
+    /// value, so counts near a bound may land in the adjacent bucket.
+    pub(crate) fn to_data(&self) -> HistogramData {
+        if let Some(exponential) = self.to_exponential_data() {
+            return exponential.to_explicit(&DEFAULT_HISTOGRAM_BOUNDS);
+        }
+        let (bounds, bucket_counts) = match &self.storage {
+            Storage::Buckets { bounds, counts } => (bounds.clone(), counts.clone()),
+            Storage::Sketch(sketch) => {
+                let bounds = DEFAULT_HISTOGRAM_BOUNDS.to_vec();
+                let mut counts = vec![0; bounds.len() + 1];
+                for (value, count) in sketch.bins() {
+                    counts[bounds.partition_point(|&b| b < value)] += count;
+                }
+                (bounds, counts)
+            }
+            Storage::Exponential(_) => unreachable!("converted above"),
+        };
+        HistogramData {
+            count: self.count,
+            sum: self.sum,
+            min: self.min,
+            max: self.max,
+            bounds,
+            bucket_counts,
+        }
+    }
+}
+
+/// A histogram split across shards, one per recording thread where cores
+/// allow, so concurrent recorders rarely contend on a lock. Readers merge
+/// the shards.
+#[derive(Debug)]
+pub(crate) struct ShardedHistogram {
+    shards: Box<[Mutex<Histogram>]>,
+}
+
+impl ShardedHistogram {
+    pub(crate) fn new(aggregation: &HistogramAggregation, shards: usize) -> Self {
+        Self {
+            shards: (0..shards).map(|_| Mutex::new(Histogram::new(aggregation))).collect(),
+        }
+    }
+
+    pub(crate) fn record(&self, value: f64) {
+        thread_local! {
+            static SHARD: usize = {
+                static NEXT: AtomicUsize = AtomicUsize::new(0);
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            };
+        }
+        let shard = SHARD.with(|shard| *shard) % self.shards.len();
+        self.shards[shard].lock().unwrap().record(value);
+    }
+
+    /// Every shard merged into one histogram
+    pub(crate) fn merged(&self) -> Histogram {
+        let mut merged = self.shards[0].lock().unwrap().clone();
+        for shard in &self.shards[1..] {
+            merged.merge(&shard.lock().unwrap());
+        }
+        merged
+    }
+
+    /// [`Self::merged`], but with min and max covering only the values
+    /// recorded since `reader`'s previous call, whose window this restarts.
+    /// A reader's first call covers every value.
+    pub(crate) fn merged_interval(&self, reader: u64) -> Histogram {
+        let mut merged: Option<Histogram> = None;
+        let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for shard in self.shards.iter() {
+            let mut shard = shard.lock().unwrap();
+            let interval = match shard.intervals.iter_mut().find(|i| i.reader == reader) {
+                Some(interval) => std::mem::replace(interval, Extremes::new(reader)),
+                None => {
+                    shard.intervals.push(Extremes::new(reader));
+                    Extremes {
+                        reader,
+                        min: shard.min,
+                        max: shard.max,
+                    }
+                }
+            };
+            (min, max) = (min.min(interval.min), max.max(interval.max));
+            match &mut merged {
+                Some(merged) => merged.merge(&shard),
+                None => merged = Some(shard.clone()),
+            }
+        }
+        let mut merged = merged.expect("at least one shard");
+        merged.min = min;
+        merged.max = max;
+        merged
+    }
+}
+
+/// 0-based rank of quantile `q` among `count` samples. Every aggregation
+/// uses this definition, which matches exact samples: with values 1 to 5,
+/// p90 lies 60% of the way from 4 to 5.
+fn quantile_rank(q: f64, count: u64) -> f64 {
+    q * count.saturating_sub(1) as f64
+}
+
+/// Value at 0-based `rank` within `(lower, upper, count)` bins in ascending
+/// order, with each bin's samples spread evenly across it, each at the
+/// middle of its share
+fn interpolate(bins: impl Iterator<Item = (f64, f64, u64)>, rank: f64) -> Option<f64> {
+    let mut seen = 0u64;
+    let mut last = None;
+    for (lower, upper, count) in bins {
+        if count == 0 {
+            continue;
+        }
+        if (seen + count) as f64 > rank {
+            let fraction = ((rank - seen as f64 + 0.5) / count as f64).clamp(0.0, 1.0);
+            return Some(lower + (upper - lower) * fraction);
+        }
+        seen += count;
+        last = Some(upper);
+    }
+    last
+}
+
+/// [`interpolate`] over explicit buckets, using the observed min and max as
+/// the outer edges of the open-ended buckets
+fn bucket_quantile(bounds: &[f64], counts: &[u64], count: u64, q: f64, min: f64, max: f64) -> f64 {
+    let bins = counts.iter().enumerate().map(|(i, &c)| {
+        let lower = if i == 0 { min } else { bounds[i - 1].max(min) };
+        let upper = if i == bounds.len() { max } else { bounds[i].min(max) };
+        (lower, upper, c)
+    });
+    interpolate(bins, quantile_rank(q, count)).unwrap_or(max)
+}
+
+/// DDSketch with logarithmically sized buckets.
+///
+/// Bucket `i` holds values in `(gamma^(i-1), gamma^i]`, where
+/// `gamma = (1 + a) / (1 - a)` for relative accuracy `a`.
+#[derive(Debug, Clone)]
+struct DdSketch {
+    gamma_ln: f64,
+    positive: DenseStore,
+    negative: DenseStore,
+    zero_count: u64,
+}
+
+/// Values smaller than this in magnitude are counted as zero
+const MIN_INDEXABLE: f64 = 1e-9;
+
+impl DdSketch {
+    fn new(relative_accuracy: f64, max_buckets: usize) -> Self {
+        let accuracy = if relative_accuracy.is_finite() {
+            relative_accuracy.clamp(1e-6, 0.5)
+        } else {
+            0.01
+        };
+        let gamma = (1.0 + accuracy) / (1.0 - accuracy);
+        Self {
+            gamma_ln: gamma.ln(),
+            positive: DenseStore::new(max_buckets),
+            negative: DenseStore::new(max_buckets),
+            zero_count: 0,
+        }
+    }
+
+    fn index(&self, value: f64) -> i32 {
+        (value.ln() / self.gamma_ln).ceil() as i32
+    }
+
+    /// Midpoint of bucket `index`, within the relative accuracy of every
+    /// value in it
+    fn value(&self, index: i32) -> f64 {
+        2.0 * (index as f64 * self.gamma_ln).exp() / (1.0 + self.gamma_ln.exp())
+    }
+
+    fn add(&mut self, value: f64) {
+        if value > MIN_INDEXABLE {
+            let index = self.index(value);
+            self.positive.add(index, 1);
+        } else if value < -MIN_INDEXABLE {
+            let index = self.index(-value);
+            self.negative.add(index, 1);
+        } else {
+            self.zero_count += 1;
+        }
+    }
+
+    /// Add `other`'s counts; both must have the same relative accuracy
+    fn merge(&mut self, other: &DdSketch) {
+        for (index, count) in other.positive.iter() {
+            self.positive.add(index, count);
+        }
+        for (index, count) in other.negative.iter() {
+            self.negative.add(index, count);
+        }
+        self.zero_count += other.zero_count;
+    }
+
+    /// Buckets in ascending value order as (representative value, count)
+    fn bins(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        let negative = self.negative.iter().rev().map(|(i, c)| (-self.value(i), c));
+        let zero = (self.zero_count > 0).then_some((0.0, self.zero_count));
+        let positive = self.positive.iter().map(|(i, c)| (self.value(i), c));
+        negative.chain(zero).chain(positive)
+    }
+
+    /// Interpolates between the values at the whole ranks either side of
+    /// [`quantile_rank`], as for exact samples
+    fn quantile(&self, q: f64) -> f64 {
+        let total = self.negative.total + self.zero_count + self.positive.total;
+        let rank = quantile_rank(q, total);
+        let (low, high) = (self.value_at_rank(rank.floor() as u64), self.value_at_rank(rank.ceil() as u64));
+        low + (high - low) * rank.fract()
+    }
+
+    /// Representative value of the `rank`-th smallest sample (0-based)
+    fn value_at_rank(&self, rank: u64) -> f64 {
+        let mut seen = 0;
+        for (value, count) in self.bins() {
+            seen += count;
+            if seen > rank {
+                return value;
+            }
+        }
+        0.0
+    }
+}
+
+/// Contiguous bucket counts starting at index `offset`, capped at
+/// `max_len` buckets by merging the lowest ones.
+///
+/// The counts live in a ring buffer, so extending the range at either end
+/// only writes the new buckets and never moves the existing ones; the
+/// buffer's own growth is amortized over the records that fill it.
+#[derive(Debug, Clone)]
+struct DenseStore {
+    offset: i32,
+    counts: VecDeque<u64>,
+    total: u64,
+    max_len: usize,
+}
+
+impl DenseStore {
+    fn new(max_len: usize) -> Self {
+        Self {
+            offset: 0,
+            counts: VecDeque::new(),
+            total: 0,
+            max_len: max_len.max(1),
+        }
+    }
+
+    fn add(&mut self, index: i32, count: u64) {
+        self.total += count;
+        if self.counts.is_empty() {
+            self.offset = index;
+            self.counts.push_back(count);
+            return;
+        }
+        if index < self.offset {
+            let grow = (self.offset - index) as usize;
+            if self.counts.len() + grow > self.max_len {
+                // Too far below the lowest bucket: it is merged into it
+                self.counts[0] += count;
+                return;
+            }
+            for _ in 0..grow {
+                self.counts.push_front(0);
+            }
+            self.offset = index;
+        }
+        let span = (index - self.offset) as usize + 1;
+        if span > self.max_len {
+            self.collapse_lowest(span - self.max_len);
+        }
+        let position = (index - self.offset) as usize;
+        if position >= self.counts.len() {
+            self.counts.resize(position + 1, 0);
+        }
+        self.counts[position] += count;
+    }
+
+    /// Fold the lowest `excess + 1` buckets into one, making room for
+    /// `excess` more at the top
+    fn collapse_lowest(&mut self, excess: usize) {
+        let drained = excess.min(self.counts.len() - 1);
+        let merged: u64 = self.counts.drain(..=drained).sum();
+        self.counts.push_front(merged);
+        self.offset += excess as i32;
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = (i32, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &c)| c > 0)
+            .map(|(i, &c)| (self.offset + i as i32, c))
+    }
+}
+
+/// Scale limits of the OpenTelemetry exponential histogram. At scale 20
+/// adjacent bounds differ by under 0.0001%; at -10 a single bucket spans
+/// every finite double.
+const MAX_SCALE: i32 = 20;
+const MIN_SCALE: i32 = -10;
+
+/// OpenTelemetry exponential histogram state. At scale `s` the base is
+/// `2^(2^-s)` and bucket `i` holds magnitudes in `(base^i, base^(i+1)]`;
+/// positive and negative values share the scale.
+#[derive(Debug, Clone)]
+struct Exponential {
+    scale: i32,
+    max_size: usize,
+    zero_count: u64,
+    positive: ExponentialStore,
+    negative: ExponentialStore,
+}
+
+impl Exponential {
+    fn new(max_size: usize, max_scale: i32) -> Self {
+        Self {
+            scale: max_scale.clamp(MIN_SCALE, MAX_SCALE),
+            max_size: max_size.max(2),
+            zero_count: 0,
+            positive: ExponentialStore::default(),
+            negative: ExponentialStore::default(),
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        let magnitude = value.abs();
+        // Subnormals are below every bucket this mapping can index
+        if magnitude < f64::MIN_POSITIVE {
+            self.zero_count += 1;
+            return;
+        }
+        self.insert(value > 0.0, map_to_index(magnitude, self.scale), 1);
+    }
+
+    /// Add `count` to bucket `index` at the current scale on one side,
+    /// downscaling first if the bucket would not fit in `max_size`
+    fn insert(&mut self, positive: bool, index: i32, count: u64) {
+        let store = if positive { &self.positive } else { &self.negative };
+        let change = store.scale_change(index, self.max_size).min(self.scale - MIN_SCALE);
+        if change > 0 {
+            self.scale -= change;
+            self.positive.downscale(change);
+            self.negative.downscale(change);
+        }
+        // Buckets nest across scales, so halving the resolution `change`
+        // times maps index i to i >> change
+        let index = index >> change;
+        if positive {
+            self.positive.add(index, count);
+        } else {
+            self.negative.add(index, count);
+        }
+    }
+
+    /// Add `other`'s counts, at the coarser of the two scales
+    fn merge(&mut self, other: &Exponential) {
+        if self.scale > other.scale {
+            let change = self.scale - other.scale;
+            self.scale = other.scale;
+            self.positive.downscale(change);
+            self.negative.downscale(change);
+        }
+        for (index, count) in other.positive.iter() {
+            self.insert(true, index >> (other.scale - self.scale), count);
+        }
+        for (index, count) in other.negative.iter() {
+            self.insert(false, index >> (other.scale - self.scale), count);
+        }
+        self.zero_count += other.zero_count;
+    }
+
+    /// Buckets in ascending value order as (lower, upper, count)
+    fn bins(&self) -> impl Iterator<Item = (f64, f64, u64)> + '_ {
+        let scale = self.scale;
+        let negative = self.negative.iter().rev().map(move |(i, c)| {
+            (-lower_boundary(i + 1, scale), -lower_boundary(i, scale), c)
+        });
+        let zero = (self.zero_count > 0).then_some((0.0, 0.0, self.zero_count));
+        let positive = self
+            .positive
+            .iter()
+            .map(move |(i, c)| (lower_boundary(i, scale), lower_boundary(i + 1, scale), c));
+        negative.chain(zero).chain(positive)
+    }
+
+    /// [`interpolate`] over the buckets
+    fn quantile(&self, q: f64) -> f64 {
+        let total = self.negative.total + self.zero_count + self.positive.total;
+        interpolate(self.bins(), quantile_rank(q, total)).unwrap_or(0.0)
+    }
+}
+
+/// Index of the bucket holding `value` (positive, normal) at `scale`.
+/// Exact powers of two are computed from the exponent so they land on the
+/// bucket they bound, as the specification requires.
+fn map_to_index(value: f64, scale: i32) -> i32 {
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let power_of_two = bits & ((1 << 52) - 1) == 0;
+    if scale <= 0 {
+        let exponent = if power_of_two { exponent - 1 } else { exponent };
+        return exponent >> -scale;
+    }
+    if power_of_two {
+        return (exponent << scale) - 1;
+    }
+    let scale_factor = (scale as f64).exp2() / std::f64::consts::LN_2;
+    (value.ln() * scale_factor).ceil() as i32 - 1
+}
+
+/// `base^index` at `scale`; may overflow to infinity near the double range
+pub(crate) fn lower_boundary(index: i32, scale: i32) -> f64 {
+    (index as f64 * (-scale as f64).exp2()).exp2()
+}
+
+/// Dense counts for one sign of an exponential histogram, starting at
+/// bucket index `offset`. Like [`DenseStore`], a ring buffer, so a record
+/// outside the range never moves the existing buckets; only a downscale,
+/// at most once per scale step, rebuilds them.
+#[derive(Debug, Clone, Default)]
+struct ExponentialStore {
+    offset: i32,
+    counts: VecDeque<u64>,
+    total: u64,
+}
+
+impl ExponentialStore {
+    /// How many times the scale must halve for `index` to fit alongside the
+    /// existing buckets in `max_size`
+    fn scale_change(&self, index: i32, max_size: usize) -> i32 {
+        if self.counts.is_empty() {
+            return 0;
+        }
+        let high = self.offset + self.counts.len() as i32 - 1;
+        let (mut low, mut high) = (self.offset.min(index) as i64, high.max(index) as i64);
+        let mut change = 0;
+        while high - low + 1 > max_size as i64 {
+            low >>= 1;
+            high >>= 1;
+            change += 1;
+        }
+        change
+    }
+
+    /// Merge buckets for a scale `change` lower
+    fn downscale(&mut self, change: i32) {
+        if self.counts.is_empty() {
+            return;
+        }
+        let offset = self.offset >> change;
+        let mut merged = VecDeque::new();
+        for (i, &c) in self.counts.iter().enumerate() {
+            let position = (((self.offset + i as i32) >> change) - offset) as usize;
+            if position >= merged.len() {
+                merged.resize(position + 1, 0);
+            }
+            merged[position] += c;
+        }
+        self.offset = offset;
+        self.counts = merged;
+    }
+
+    fn add(&mut self, index: i32, count: u64) {
+        self.total += count;
+        if self.counts.is_empty() {
+            self.offset = index;
+            self.counts.push_back(count);
+            return;
+        }
+        if index < self.offset {
+            let grow = (self.offset - index) as usize;
+            for _ in 0..grow {
+                self.counts.push_front(0);
+            }
+            self.offset = index;
+        }
+        let position = (index - self.offset) as usize;
+        if position >= self.counts.len() {
+            self.counts.resize(position + 1, 0);
+        }
+        self.counts[position] += count;
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = (i32, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &c)| c > 0)
+            .map(|(i, &c)| (self.offset + i as i32, c))
+    }
+
+    fn to_data(&self) -> ExponentialBuckets {
+        ExponentialBuckets {
+            offset: self.offset,
+            bucket_counts: self.counts.iter().copied().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sketch(accuracy: f64, max_buckets: usize) -> Histogram {
+        Histogram::new(&HistogramAggregation::Sketch {
+            relative_accuracy: accuracy,
+            max_buckets,
+        })
+    }
+
+    #[test]
+    fn test_sketch_relative_accuracy() {
+        let mut h = sketch(0.01, 2048);
+        for v in 1..=10_000 {
+            h.record(v as f64);
+        }
+        for (q, exact) in [(0.5, 5000.0), (0.9, 9000.0), (0.99, 9900.0)] {
+            let estimate = h.quantile(q).unwrap();
+            assert!((estimate - exact).abs() / exact <= 0.011, "q{q}: {estimate}");
+        }
+        assert_eq!(h.quantile(0.0), Some(1.0));
+        assert_eq!(h.quantile(1.0), Some(10_000.0));
+    }
+
+    #[test]
+    fn test_dense_store_grows_at_both_ends() {
+        let mut s = DenseStore::new(4);
+        for index in [10, 8, 12] {
+            s.add(index, 1);
+        }
+        // 12 pushes the range past four buckets, so 8 and 9 fold into 9
+        assert_eq!(s.iter().collect::<Vec<_>>(), [(9, 1), (10, 1), (12, 1)]);
+        s.add(5, 2);
+        assert_eq!(s.iter().next(), Some((9, 3)), "below a full store merges into the lowest bucket");
+        s.add(30, 1);
+        assert_eq!(s.iter().collect::<Vec<_>>(), [(27, 5), (30, 1)]);
+        assert_eq!(s.counts.len(), 4);
+        assert_eq!(s.total, 6);
+    }
+
+    #[test]
+    fn test_sketch_memory_bounded() {
+        let mut h = sketch(0.01, 64);
+        for i in 0..100_000 {
+            h.record(1e-6 * 1.001f64.powi(i));
+        }
+        let Storage::Sketch(s) = &h.storage else { unreachable!() };
+        assert!(s.positive.counts.len() <= 64);
+        assert_eq!(s.positive.total, 100_000);
+        // High quantiles keep their accuracy after the low buckets collapse
+        let exact = 1e-6 * 1.001f64.powi(98_999);
+        assert!((h.quantile(0.99).unwrap() - exact).abs() / exact <= 0.011);
+    }
+
+    #[test]
+    fn test_sketch_negative_and_zero() {
+        let mut h = sketch(0.01, 128);
+        for v in [-100.0, -1.0, 0.0, 0.0, 1.0, 100.0, f64::NAN] {
+            h.record(v);
+        }
+        assert_eq!(h.count(), 6);
+        assert_eq!(h.quantile(0.0), Some(-100.0));
+        assert_eq!(h.quantile(0.5), Some(0.0));
+        let data = h.to_data();
+        assert_eq!(data.bucket_counts.iter().sum::<u64>(), 6);
+        assert_eq!(data.bucket_counts[0], 4, "values <= 0 fall in the first bucket");
+    }
+
+    #[test]
+    fn test_bucket_quantile_interpolation() {
+        let mut h = Histogram::new(&HistogramAggregation::ExplicitBuckets {
+            bounds: vec![10.0, 20.0, 30.0],
+        });
+        for v in [12.0, 14.0, 16.0, 18.0] {
+            h.record(v);
+        }
+        // All four values sit in (10, 20]; the observed min/max narrow it
+        assert_eq!(h.quantile(0.5), Some(15.0));
+        assert_eq!(h.to_data().bucket_counts, vec![0, 4, 0, 0]);
+    }
+
+    #[test]
+    fn test_exponential_index_mapping() {
+        // Scale 0: bucket i holds (2^i, 2^(i+1)]
+        assert_eq!(map_to_index(1.0, 0), -1);
+        assert_eq!(map_to_index(1.5, 0), 0);
+        assert_eq!(map_to_index(2.0, 0), 0);
+        assert_eq!(map_to_index(4.0, -1), 0);
+        assert_eq!(map_to_index(5.0, -1), 1);
+        // Scale 1: base sqrt(2)
+        assert_eq!(map_to_index(2.0, 1), 1);
+        assert_eq!(map_to_index(1.5, 1), 1);
+        assert_eq!(map_to_index(1.4, 1), 0);
+        assert_eq!(lower_boundary(3, 1), 2.0f64.powf(1.5));
+    }
+
+    #[test]
+    fn test_exponential_downscales_to_fit() {
+        let mut h = Histogram::new(&HistogramAggregation::exponential());
+        // kv_cache ops in microseconds up to model loads in minutes, as ms
+        for v in [0.001, 0.002, 0.05, 1.0, 30.0, 1_000.0, 120_000.0] {
+            h.record(v);
+        }
+        h.record(0.0);
+        h.record(-3.0);
+        let data = h.to_exponential_data().unwrap();
+        assert!(data.scale < MAX_SCALE);
+        assert!(data.positive.bucket_counts.len() <= 160);
+        assert_eq!(data.positive.bucket_counts.iter().sum::<u64>(), 7);
+        assert_eq!(data.negative.bucket_counts.iter().sum::<u64>(), 1);
+        assert_eq!(data.zero_count, 1);
+        // Every value still falls inside its bucket's bounds
+        let Storage::Exponential(e) = &h.storage else { unreachable!() };
+        for v in [0.001, 30.0, 120_000.0] {
+            let index = map_to_index(v, data.scale);
+            assert!(lower_boundary(index, data.scale) < v && v <= lower_boundary(index + 1, data.scale) * (1.0 + 1e-12));
+            assert!(e.positive.counts[(index - data.positive.offset) as usize] > 0);
+        }
+    }
+
+    #[test]
+    fn test_exponential_quantiles() {
+        let mut h = Histogram::new(&HistogramAggregation::exponential());
+        for v in 1..=10_000 {
+            h.record(v as f64);
+        }
+        let data = h.to_exponential_data().unwrap();
+        // 160 buckets over 1..10^4 leaves scale 3: bounds about 9% apart
+        assert_eq!(data.scale, 3);
+        for (q, exact) in [(0.5, 5000.0), (0.99, 9900.0)] {
+            let estimate = h.quantile(q).unwrap();
+            assert!((estimate - exact).abs() / exact <= 0.09, "q{q}: {estimate}");
+        }
+        let stats = h.stats().unwrap();
+        assert_eq!(stats.count, 10_000);
+        assert_eq!(h.to_data().bucket_counts.iter().sum::<u64>(), 10_000);
+    }
+
+    #[test]
+    fn test_quantiles_agree_across_aggregations() {
+        let bounds: Vec<f64> = (1..1_000).map(|i| i as f64 * 10.0).collect();
+        let aggregations = [
+            // 1% relative error
+            (HistogramAggregation::default(), 0.01, 0.0),
+            // Scale 3 buckets are 2^(1/8) apart
+            (HistogramAggregation::exponential(), 2f64.powf(0.125) - 1.0, 0.0),
+            // Buckets 10 wide
+            (HistogramAggregation::ExplicitBuckets { bounds }, 0.0, 10.0),
+        ];
+        for (aggregation, relative, absolute) in aggregations {
+            let mut h = Histogram::new(&aggregation);
+            for v in 1..=10_000 {
+                h.record(v as f64);
+            }
+            for q in [0.01, 0.25, 0.5, 0.9, 0.99, 0.999] {
+                // Rank q * (count - 1) among 1..=10000
+                let exact = 1.0 + q * 9_999.0;
+                let estimate = h.quantile(q).unwrap();
+                let error = (estimate - exact).abs();
+                assert!(error <= exact * relative + absolute, "{aggregation:?} q{q}: {estimate} vs {exact}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sketch_interpolates_between_ranks() {
+        let mut h = sketch(0.001, 2048);
+        for v in [10.0, 20.0, 30.0, 40.0, 50.0] {
+            h.record(v);
+        }
+        // Rank 0.9 * 4 = 3.6 lies 60% of the way from 40 to 50
+        assert!((h.quantile(0.9).unwrap() - 46.0).abs() <= 0.05);
+        let stats = h.stats().unwrap();
+        assert!((stats.stddev - 200f64.sqrt()).abs() < 1e-9);
+        assert_eq!((stats.min, stats.max), (10.0, 50.0));
+        assert!(stats.p50 <= stats.p90 && stats.p90 <= stats.p95 && stats.p95 <= stats.p99 && stats.p99 <= stats.p999);
+    }
+
+    #[test]
+    fn test_merge_matches_single_histogram() {
+        for aggregation in [
+            HistogramAggregation::default(),
+            HistogramAggregation::exponential(),
+            HistogramAggregation::ExplicitBuckets { bounds: vec![1.0, 100.0] },
+        ] {
+            let mut whole = Histogram::new(&aggregation);
+            let mut parts = [Histogram::new(&aggregation), Histogram::new(&aggregation)];
+            for i in 0..2_000 {
+                let v = 0.01 * 1.005f64.powi(i) - 1.0;
+                whole.record(v);
+                parts[(i % 7 == 0) as usize].record(v);
+            }
+            let [mut merged, other] = parts;
+            merged.merge(&other);
+            let (a, b) = (merged.stats().unwrap(), whole.stats().unwrap());
+            assert_eq!((a.count, a.min, a.max), (b.count, b.min, b.max));
+            assert!((a.stddev - b.stddev).abs() / b.stddev < 1e-9);
+            assert_eq!(merged.to_data().bucket_counts, whole.to_data().bucket_counts, "{aggregation:?}");
+        }
+    }
+
+    #[test]
+    fn test_sharded_histogram_across_threads() {
+        let sharded = std::sync::Arc::new(ShardedHistogram::new(&HistogramAggregation::exponential(), 8));
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let sharded = std::sync::Arc::clone(&sharded);
+                std::thread::spawn(move || (0..1_000).for_each(|i| sharded.record((t * 1_000 + i) as f64)))
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        let merged = sharded.merged();
+        assert_eq!(merged.count(), 8_000);
+        assert_eq!(merged.quantile(1.0), Some(7_999.0));
+    }
+
+    #[test]
+    fn test_shard_count() {
+        let config = |shards| HistogramConfig {
+            shards,
+            ..Default::default()
+        };
+        assert_eq!(config(Some(1)).shard_count(), 1);
+        assert_eq!(config(Some(0)).shard_count(), 1);
+        assert!((1..=MAX_DEFAULT_SHARDS).contains(&config(None).shard_count()));
+        let single = ShardedHistogram::new(&HistogramAggregation::default(), config(Some(1)).shard_count());
+        assert_eq!(single.shards.len(), 1);
+    }
+
+    #[test]
+    fn test_merged_interval_restarts_extremes() {
+        let sharded = ShardedHistogram::new(&HistogramAggregation::default(), 4);
+        [5.0, 50.0].into_iter().for_each(|v| sharded.record(v));
+        let first = sharded.merged_interval(0).to_data();
+        assert_eq!((first.min, first.max), (5.0, 50.0));
+
+        sharded.record(20.0);
+        let second = sharded.merged_interval(0).to_data();
+        assert_eq!((second.count, second.min, second.max), (3, 20.0, 20.0));
+        let other = sharded.merged_interval(1).to_data();
+        assert_eq!((other.min, other.max), (5.0, 50.0), "each reader has its own window");
+        let cumulative = sharded.merged().to_data();
+        assert_eq!((cumulative.min, cumulative.max), (5.0, 50.0));
+    }
+}
@@ -0,0 +1,96 @@
+//! Timing async work as spans.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+
+use crate::{OwnedSpanGuard, SigmaTelemetry, SpanOperation};
+
+pin_project! {
+    /// Future that ends its span when the wrapped future completes.
+    ///
+    /// The span is timed from when the guard was started, so it includes time
+    /// spent waiting to be polled. Dropping the future early ends the span just
+    /// as dropping a [`crate::SpanGuard`] does.
+    pub struct Instrumented<F> {
+        #[pin]
+        inner: F,
+        span: Option<OwnedSpanGuard>,
+    }
+}
+
+impl<F> Instrumented<F> {
+    /// The span this future will end, until it completes
+    pub fn span(&self) -> Option<&OwnedSpanGuard> {
+        self.span.as_ref()
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        match this.inner.poll(cx) {
+            Poll::Ready(output) => {
+                drop(this.span.take());
+                Poll::Ready(output)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Attach an owned span to a future
+pub trait Instrument: Future + Sized {
+    fn instrument(self, span: OwnedSpanGuard) -> Instrumented<Self> {
+        Instrumented {
+            inner: self,
+            span: Some(span),
+        }
+    }
+}
+
+impl<F: Future> Instrument for F {}
+
+impl SigmaTelemetry {
+    /// Run `future` inside a new root span
+    pub fn instrument<F: Future>(self: &Arc<Self>, name: &str, operation: SpanOperation, future: F) -> Instrumented<F> {
+        future.instrument(self.start_owned_span(name, operation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_instrumented_future_records_on_completion() {
+        let t = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+        let value = t
+            .instrument("prefill", SpanOperation::Inference, async {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                7
+            })
+            .await;
+        assert_eq!(value, 7);
+        assert_eq!(t.snapshot().span_count, 1);
+        let stats = t.metrics().get_histogram_stats("span.inference.duration_ms").unwrap();
+        assert!(stats.sum >= 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_instrumented_future_dropped_early() {
+        let t = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+        let fut = std::future::pending::<()>().instrument(t.start_owned_span("stalled", SpanOperation::Inference));
+        assert!(fut.span().is_some());
+        assert_eq!(t.snapshot().span_count, 0);
+        drop(fut);
+        assert_eq!(t.snapshot().span_count, 1);
+    }
+}
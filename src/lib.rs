@@ -4,21 +4,54 @@
 //! Provides structured tracing, metrics, and log correlation for inference
 //! pipelines, model loading, and agent orchestration.
 
+mod buffer;
+pub mod attributes;
 pub mod config;
+pub mod context;
 pub mod error;
+pub mod global;
+pub mod histogram;
+pub mod instrument;
+pub mod logs;
 pub mod metrics;
+#[cfg(feature = "jaeger")]
+pub mod otlp_grpc;
+#[cfg(feature = "protobuf")]
+pub mod otlp_proto;
+pub mod processor;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
 pub mod spans;
 pub mod exporter;
+pub mod propagation;
+pub mod rates;
 pub mod ryzanstein_integration;
+pub mod sampling;
+pub mod tail_sampling;
+pub mod temporality;
+#[cfg(test)]
+mod test_support;
 
+use std::ops::Deref;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use config::TelemetryConfig;
 
+pub use attributes::{AttributeValue, Attributes, MetricAttributes};
+pub use context::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+pub use instrument::{Instrument, Instrumented};
+pub use logs::{LogRecord, Severity};
+pub use temporality::{MetricReader, Temporality};
+
 /// Core telemetry system for Ryzanstein
 pub struct SigmaTelemetry {
     config: TelemetryConfig,
     metrics: MetricsCollector,
-    active_spans: std::sync::Mutex<Vec<SpanRecord>>,
+    active_spans: buffer::SpanQueue,
+    logs: buffer::LogQueue,
+    sampler: Box<dyn sampling::Sampler>,
+    tail_sampler: Option<tail_sampling::TailSampler>,
 }
 
 /// Recorded span information
@@ -27,12 +60,58 @@ pub struct SpanRecord {
     pub name: String,
     pub service: String,
     pub operation: SpanOperation,
+    pub kind: SpanKind,
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    /// `None` for the root span of a trace
+    pub parent_span_id: Option<SpanId>,
+    /// Vendor `tracestate` inherited from the parent, exported with the span
+    pub trace_state: TraceState,
+    /// The parent was extracted from another process, making this span the
+    /// local root of its trace
+    pub parent_is_remote: bool,
     pub start_time: std::time::SystemTime,
     pub duration: Option<Duration>,
-    pub attributes: Vec<(String, String)>,
+    pub attributes: Attributes,
+    pub events: Vec<SpanEvent>,
+    /// Causal links to spans in other traces, fixed at start time
+    pub links: Vec<SpanLink>,
     pub status: SpanStatus,
 }
 
+/// Reference from a span to another span, typically in a different trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanLink {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub trace_state: TraceState,
+    pub attributes: Attributes,
+}
+
+impl SpanLink {
+    pub fn new(context: &SpanContext) -> Self {
+        Self {
+            trace_id: context.trace_id,
+            span_id: context.span_id,
+            trace_state: context.trace_state.clone(),
+            attributes: Attributes::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, key: &str, value: impl Into<AttributeValue>) -> Self {
+        self.attributes.insert(key, value);
+        self
+    }
+}
+
+/// Timestamped milestone within a span, e.g. "first_token"
+#[derive(Debug, Clone)]
+pub struct SpanEvent {
+    pub name: String,
+    pub timestamp: std::time::SystemTime,
+    pub attributes: Attributes,
+}
+
 /// Well-known span operations for Ryzanstein
 #[derive(Debug, Clone, PartialEq)]
 pub enum SpanOperation {
@@ -67,6 +146,60 @@ impl std::fmt::Display for SpanOperation {
     }
 }
 
+/// Role of a span in a request, used by backends to draw service maps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanKind {
+    /// Work inside the service with no remote side
+    #[default]
+    Internal,
+    /// Handling an incoming synchronous request, e.g. an HTTP handler
+    Server,
+    /// Making an outgoing synchronous request
+    Client,
+    /// Enqueueing a message for later processing
+    Producer,
+    /// Processing a message taken from a queue
+    Consumer,
+}
+
+/// Optional settings applied when a span starts
+#[derive(Debug, Clone, Default)]
+pub struct SpanOptions {
+    pub kind: SpanKind,
+    /// Parent to continue; `None` or an invalid context starts a new trace
+    pub parent: Option<SpanContext>,
+    pub links: Vec<SpanLink>,
+    /// Attributes set before the span starts
+    pub attributes: Attributes,
+}
+
+impl SpanOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_kind(mut self, kind: SpanKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_parent(mut self, parent: &SpanContext) -> Self {
+        self.parent = Some(parent.clone());
+        self
+    }
+
+    pub fn with_links(mut self, links: Vec<SpanLink>) -> Self {
+        self.links = links;
+        self
+    }
+
+    pub fn with_attribute(mut self, key: &str, value: impl Into<AttributeValue>) -> Self {
+        self.attributes.insert(key, value);
+        self
+    }
+}
+
 /// Span status
 #[derive(Debug, Clone, PartialEq)]
 pub enum SpanStatus {
@@ -75,20 +208,295 @@ pub enum SpanStatus {
     Unset,
 }
 
-/// Metrics collector
+/// Metrics collector. Every instrument takes an optional attribute set; each
+/// distinct set under a name is its own series, and the plain methods use
+/// the empty set.
+///
+/// Series cells are shared with the [`metrics::Counter`], [`metrics::Gauge`]
+/// and [`metrics::Histogram`] handles, which record without touching the
+/// maps here.
 pub struct MetricsCollector {
-    counters: std::sync::Mutex<std::collections::HashMap<String, u64>>,
-    histograms: std::sync::Mutex<std::collections::HashMap<String, Vec<f64>>>,
-    gauges: std::sync::Mutex<std::collections::HashMap<String, f64>>,
+    counters: std::sync::Mutex<SeriesMap<Arc<AtomicU64>>>,
+    histograms: std::sync::Mutex<SeriesMap<Arc<histogram::ShardedHistogram>>>,
+    gauges: std::sync::Mutex<SeriesMap<Arc<metrics::GaugeCell>>>,
+    up_down_counters: std::sync::Mutex<SeriesMap<Arc<AtomicI64>>>,
+    observables: std::sync::Mutex<Vec<Arc<metrics::ObservableInstrument>>>,
+    next_callback_id: AtomicU64,
+    histogram_config: histogram::HistogramConfig,
+    start_time: std::time::SystemTime,
+}
+
+/// Series by metric name, then attribute set
+type SeriesMap<V> = std::collections::HashMap<String, std::collections::HashMap<MetricAttributes, V>>;
+
+/// The series `name`/`attributes`, created with `init` if missing. Looks the
+/// name up by reference so recording to an existing metric doesn't allocate
+/// for it.
+fn series_entry<'a, V>(
+    map: &'a mut SeriesMap<V>,
+    name: &str,
+    attributes: &[(&str, &str)],
+    init: impl FnOnce() -> V,
+) -> &'a mut V {
+    if !map.contains_key(name) {
+        map.insert(name.to_string(), std::collections::HashMap::new());
+    }
+    map.get_mut(name)
+        .unwrap()
+        .entry(MetricAttributes::new(attributes))
+        .or_insert_with(init)
+}
+
+fn series_get<'a, V>(map: &'a SeriesMap<V>, name: &str, attributes: &[(&str, &str)]) -> Option<&'a V> {
+    map.get(name)?.get(&MetricAttributes::new(attributes))
+}
+
+/// Every series for which `value` returns `Some`, sorted by name then
+/// attributes
+fn collect_series<V, T>(map: &SeriesMap<V>, value: impl Fn(&V) -> Option<T>) -> Vec<Series<T>> {
+    let mut series: Vec<Series<T>> = map
+        .iter()
+        .flat_map(|(name, by_attributes)| {
+            by_attributes.iter().filter_map(|(attributes, v)| {
+                Some(Series {
+                    name: name.clone(),
+                    attributes: attributes.clone(),
+                    value: value(v)?,
+                })
+            })
+        })
+        .collect();
+    sort_series(&mut series);
+    series
+}
+
+fn sort_series<T>(series: &mut [Series<T>]) {
+    series.sort_by(|a, b| (&a.name, &a.attributes).cmp(&(&b.name, &b.attributes)));
+}
+
+/// Sort series with observations appended, keeping only the first of any
+/// with the same name and attributes. The sort is stable, so synchronous
+/// series win over observed ones.
+fn merge_observed<T>(series: &mut Vec<Series<T>>) {
+    sort_series(series);
+    series.dedup_by(|later, first| later.name == first.name && later.attributes == first.attributes);
 }
 
+/// Bucket boundaries used when exporting sketch histograms, the
+/// OpenTelemetry SDK defaults
+pub const DEFAULT_HISTOGRAM_BOUNDS: [f64; 15] = [
+    0.0, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 2500.0, 5000.0, 7500.0, 10000.0,
+];
+
 impl MetricsCollector {
-    fn new() -> Self {
+    fn new(histogram_config: histogram::HistogramConfig) -> Self {
         Self {
             counters: std::sync::Mutex::new(std::collections::HashMap::new()),
             histograms: std::sync::Mutex::new(std::collections::HashMap::new()),
             gauges: std::sync::Mutex::new(std::collections::HashMap::new()),
+            up_down_counters: std::sync::Mutex::new(std::collections::HashMap::new()),
+            observables: std::sync::Mutex::new(Vec::new()),
+            next_callback_id: AtomicU64::new(0),
+            histogram_config,
+            start_time: std::time::SystemTime::now(),
+        }
+    }
+
+    /// Cumulative [`Self::collect`], as for Prometheus scrapes
+    pub fn export_data(&self) -> MetricsData {
+        self.cumulative_data(None)
+    }
+
+    /// Copy every metric for export in `reader`'s temporality, first
+    /// invoking the observable instruments' callbacks. Series are sorted by
+    /// name, then attributes.
+    ///
+    /// Cumulative values cover everything since the collector was created.
+    /// Delta values cover the interval since the reader's previous
+    /// collection, which is each point's start time, and leave out series
+    /// without new measurements; gauges are reported as they are either way.
+    pub fn collect(&self, reader: &MetricReader) -> MetricsData {
+        reader.collect(self)
+    }
+
+    /// With a delta `reader` ID, histogram min and max cover only the values
+    /// recorded since that reader's previous call
+    pub(crate) fn cumulative_data(&self, reader: Option<u64>) -> MetricsData {
+        let merged = collect_series(&self.histograms.lock().unwrap(), |h| {
+            Some(reader.map_or_else(|| h.merged(), |reader| h.merged_interval(reader))).filter(|h| h.count() > 0)
+        });
+        let mut histograms = Vec::new();
+        let mut exponential_histograms = Vec::new();
+        for Series { name, attributes, value } in merged {
+            match value.to_exponential_data() {
+                Some(data) => exponential_histograms.push(Series::new(name, attributes, data)),
+                None => histograms.push(Series::new(name, attributes, value.to_data())),
+            }
+        }
+        let mut data = MetricsData {
+            start_time_unix_nano: exporter::unix_nanos(self.start_time),
+            time_unix_nano: exporter::unix_nanos(std::time::SystemTime::now()),
+            temporality: Temporality::Cumulative,
+            counters: collect_series(&self.counters.lock().unwrap(), |v| Some(v.load(Ordering::Relaxed))),
+            gauges: collect_series(&self.gauges.lock().unwrap(), |v| v.get()),
+            up_down_counters: collect_series(&self.up_down_counters.lock().unwrap(), |v| {
+                Some(v.load(Ordering::Relaxed))
+            }),
+            histograms,
+            exponential_histograms,
+        };
+        // Cloned out so callbacks may use the collector, even to register
+        let observables = self.observables.lock().unwrap().clone();
+        if !observables.is_empty() {
+            for observable in &observables {
+                observable.observe(&mut data);
+            }
+            merge_observed(&mut data.counters);
+            merge_observed(&mut data.gauges);
+            merge_observed(&mut data.up_down_counters);
+        }
+        data
+    }
+
+    /// Handle to the up-down counter `name`, for values that rise and fall
+    /// such as active sequences or queued requests
+    pub fn up_down_counter(&self, name: &str) -> metrics::UpDownCounter {
+        self.up_down_counter_with(name, &[])
+    }
+
+    /// [`Self::up_down_counter`] for the series for `attributes`
+    pub fn up_down_counter_with(&self, name: &str, attributes: &[(&str, &str)]) -> metrics::UpDownCounter {
+        let mut counters = self.up_down_counters.lock().unwrap();
+        metrics::UpDownCounter::new(Arc::clone(series_entry(&mut counters, name, attributes, Default::default)))
+    }
+
+    /// Add `delta`, possibly negative, to an up-down counter
+    pub fn add_up_down(&self, name: &str, delta: i64) {
+        self.add_up_down_with(name, delta, &[]);
+    }
+
+    /// Add `delta` to the up-down counter series for `attributes`
+    pub fn add_up_down_with(&self, name: &str, delta: i64, attributes: &[(&str, &str)]) {
+        let mut counters = self.up_down_counters.lock().unwrap();
+        series_entry(&mut counters, name, attributes, Default::default).fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// Get up-down counter value
+    pub fn get_up_down_counter(&self, name: &str) -> i64 {
+        self.get_up_down_counter_with(name, &[])
+    }
+
+    /// Get the up-down counter series for `attributes`
+    pub fn get_up_down_counter_with(&self, name: &str, attributes: &[(&str, &str)]) -> i64 {
+        series_get(&self.up_down_counters.lock().unwrap(), name, attributes).map_or(0, |v| v.load(Ordering::Relaxed))
+    }
+
+    /// Register a callback reporting the cumulative total of counter `name`
+    /// each time metrics are collected, e.g. from a counter kept elsewhere
+    pub fn observe_counter(
+        &self,
+        name: &str,
+        callback: impl Fn(&mut metrics::Observer<u64>) + Send + Sync + 'static,
+    ) -> metrics::CallbackId {
+        self.register_observable(name, metrics::Callback::Counter(Box::new(callback)))
+    }
+
+    /// Register a callback sampling gauge `name` each time metrics are
+    /// collected
+    pub fn observe_gauge(
+        &self,
+        name: &str,
+        callback: impl Fn(&mut metrics::Observer<f64>) + Send + Sync + 'static,
+    ) -> metrics::CallbackId {
+        self.register_observable(name, metrics::Callback::Gauge(Box::new(callback)))
+    }
+
+    /// Register a callback reporting the current value of up-down counter
+    /// `name` each time metrics are collected
+    pub fn observe_up_down_counter(
+        &self,
+        name: &str,
+        callback: impl Fn(&mut metrics::Observer<i64>) + Send + Sync + 'static,
+    ) -> metrics::CallbackId {
+        self.register_observable(name, metrics::Callback::UpDownCounter(Box::new(callback)))
+    }
+
+    /// Unregister an observable instrument's callback; false if it was
+    /// already removed
+    pub fn remove_callback(&self, id: metrics::CallbackId) -> bool {
+        let mut observables = self.observables.lock().unwrap();
+        let before = observables.len();
+        observables.retain(|o| o.id != id);
+        observables.len() < before
+    }
+
+    /// A name a synchronous instrument already uses is allowed, with a
+    /// warning; where their series clash the synchronous one is exported
+    fn register_observable(&self, name: &str, callback: metrics::Callback) -> metrics::CallbackId {
+        let synchronous = self.counters.lock().unwrap().contains_key(name)
+            || self.gauges.lock().unwrap().contains_key(name)
+            || self.up_down_counters.lock().unwrap().contains_key(name)
+            || self.histograms.lock().unwrap().contains_key(name);
+        if synchronous {
+            tracing::warn!(metric = name, "observable instrument shares its name with a synchronous instrument");
         }
+        let id = metrics::CallbackId(self.next_callback_id.fetch_add(1, Ordering::Relaxed));
+        let observable = metrics::ObservableInstrument::new(id, name, callback);
+        self.observables.lock().unwrap().push(Arc::new(observable));
+        id
+    }
+
+    /// Handle to the counter `name`, registering it at zero. Recording
+    /// through the handle is one atomic add, with no lock, map lookup or
+    /// allocation, for hot paths such as per-token decode loops.
+    pub fn counter(&self, name: &str) -> metrics::Counter {
+        self.counter_with(name, &[])
+    }
+
+    /// [`Self::counter`] for the series for `attributes`
+    pub fn counter_with(&self, name: &str, attributes: &[(&str, &str)]) -> metrics::Counter {
+        metrics::Counter::new(self.counter_cell(name, attributes))
+    }
+
+    /// Handle to the gauge `name`; the series is exported once set
+    pub fn gauge(&self, name: &str) -> metrics::Gauge {
+        self.gauge_with(name, &[])
+    }
+
+    /// [`Self::gauge`] for the series for `attributes`
+    pub fn gauge_with(&self, name: &str, attributes: &[(&str, &str)]) -> metrics::Gauge {
+        metrics::Gauge::new(self.gauge_cell(name, attributes))
+    }
+
+    /// Handle to the histogram `name`. Values go to a per-thread shard, so
+    /// threads recording concurrently rarely wait on each other.
+    pub fn histogram(&self, name: &str) -> metrics::Histogram {
+        self.histogram_with(name, &[])
+    }
+
+    /// [`Self::histogram`] for the series for `attributes`
+    pub fn histogram_with(&self, name: &str, attributes: &[(&str, &str)]) -> metrics::Histogram {
+        metrics::Histogram::new(self.histogram_cell(name, attributes))
+    }
+
+    fn counter_cell(&self, name: &str, attributes: &[(&str, &str)]) -> Arc<AtomicU64> {
+        let mut counters = self.counters.lock().unwrap();
+        Arc::clone(series_entry(&mut counters, name, attributes, Default::default))
+    }
+
+    fn gauge_cell(&self, name: &str, attributes: &[(&str, &str)]) -> Arc<metrics::GaugeCell> {
+        let mut gauges = self.gauges.lock().unwrap();
+        Arc::clone(series_entry(&mut gauges, name, attributes, Default::default))
+    }
+
+    fn histogram_cell(&self, name: &str, attributes: &[(&str, &str)]) -> Arc<histogram::ShardedHistogram> {
+        let mut histograms = self.histograms.lock().unwrap();
+        Arc::clone(series_entry(&mut histograms, name, attributes, || {
+            Arc::new(histogram::ShardedHistogram::new(
+                self.histogram_config.aggregation_for(name),
+                self.histogram_config.shard_count(),
+            ))
+        }))
     }
 
     /// Increment a counter by 1
@@ -98,58 +506,221 @@ impl MetricsCollector {
 
     /// Increment a counter by a specific amount
     pub fn increment_by(&self, name: &str, value: u64) {
+        self.increment_by_with(name, value, &[]);
+    }
+
+    /// Increment the counter series for `attributes` by 1, e.g.
+    /// `increment_with("ryzanstein.inference.requests", &[("model", "bitnet-3b")])`
+    pub fn increment_with(&self, name: &str, attributes: &[(&str, &str)]) {
+        self.increment_by_with(name, 1, attributes);
+    }
+
+    /// Increment the counter series for `attributes` by `value`
+    pub fn increment_by_with(&self, name: &str, value: u64, attributes: &[(&str, &str)]) {
         let mut counters = self.counters.lock().unwrap();
-        *counters.entry(name.to_string()).or_insert(0) += value;
+        series_entry(&mut counters, name, attributes, Default::default).fetch_add(value, Ordering::Relaxed);
     }
 
-    /// Record a histogram value (e.g., latency)
+    /// Record a histogram value (e.g., latency), aggregated as configured
+    /// for `name` in [`histogram::HistogramConfig`]
     pub fn record_histogram(&self, name: &str, value: f64) {
-        let mut histograms = self.histograms.lock().unwrap();
-        histograms.entry(name.to_string()).or_default().push(value);
+        self.record_histogram_with(name, value, &[]);
+    }
+
+    /// Record a value in the histogram series for `attributes`
+    pub fn record_histogram_with(&self, name: &str, value: f64, attributes: &[(&str, &str)]) {
+        self.histogram_cell(name, attributes).record(value);
     }
 
     /// Set a gauge value
     pub fn set_gauge(&self, name: &str, value: f64) {
-        let mut gauges = self.gauges.lock().unwrap();
-        gauges.insert(name.to_string(), value);
+        self.set_gauge_with(name, value, &[]);
+    }
+
+    /// Set the gauge series for `attributes`
+    pub fn set_gauge_with(&self, name: &str, value: f64, attributes: &[(&str, &str)]) {
+        self.gauge_cell(name, attributes).set(value);
     }
 
     /// Get counter value
     pub fn get_counter(&self, name: &str) -> u64 {
-        self.counters.lock().unwrap().get(name).copied().unwrap_or(0)
+        self.get_counter_with(name, &[])
+    }
+
+    /// Get the counter series for `attributes`
+    pub fn get_counter_with(&self, name: &str, attributes: &[(&str, &str)]) -> u64 {
+        series_get(&self.counters.lock().unwrap(), name, attributes).map_or(0, |v| v.load(Ordering::Relaxed))
+    }
+
+    /// Sum of every series of a counter, whatever its attributes
+    pub fn get_counter_total(&self, name: &str) -> u64 {
+        let counters = self.counters.lock().unwrap();
+        counters.get(name).map_or(0, |series| series.values().map(|v| v.load(Ordering::Relaxed)).sum())
     }
 
     /// Get gauge value
     pub fn get_gauge(&self, name: &str) -> Option<f64> {
-        self.gauges.lock().unwrap().get(name).copied()
+        self.get_gauge_with(name, &[])
+    }
+
+    /// Get the gauge series for `attributes`
+    pub fn get_gauge_with(&self, name: &str, attributes: &[(&str, &str)]) -> Option<f64> {
+        series_get(&self.gauges.lock().unwrap(), name, attributes)?.get()
     }
 
     /// Get histogram statistics
     pub fn get_histogram_stats(&self, name: &str) -> Option<HistogramStats> {
-        let histograms = self.histograms.lock().unwrap();
-        let values = histograms.get(name)?;
-        if values.is_empty() {
-            return None;
-        }
-        let sum: f64 = values.iter().sum();
-        let count = values.len();
-        let mean = sum / count as f64;
-        let mut sorted = values.clone();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let p50 = sorted[count / 2];
-        let p99 = sorted[(count as f64 * 0.99) as usize];
-        Some(HistogramStats { count, sum, mean, p50, p99 })
+        self.get_histogram_stats_with(name, &[])
+    }
+
+    /// Get statistics for the histogram series for `attributes`
+    pub fn get_histogram_stats_with(&self, name: &str, attributes: &[(&str, &str)]) -> Option<HistogramStats> {
+        let histogram = series_get(&self.histograms.lock().unwrap(), name, attributes)?.merged();
+        histogram.stats()
+    }
+
+    /// Interpolated values at each of `quantiles` (0.0..=1.0), in the same
+    /// order; `None` if nothing was recorded under `name`
+    pub fn get_histogram_quantiles(&self, name: &str, quantiles: &[f64]) -> Option<Vec<f64>> {
+        self.get_histogram_quantiles_with(name, quantiles, &[])
+    }
+
+    /// [`Self::get_histogram_quantiles`] for the series for `attributes`
+    pub fn get_histogram_quantiles_with(
+        &self,
+        name: &str,
+        quantiles: &[f64],
+        attributes: &[(&str, &str)],
+    ) -> Option<Vec<f64>> {
+        let histogram = series_get(&self.histograms.lock().unwrap(), name, attributes)?.merged();
+        quantiles.iter().map(|&q| histogram.quantile(q)).collect()
     }
 }
 
-/// Histogram statistics
+/// Histogram statistics. Quantiles are estimates within the accuracy of the
+/// metric's [`histogram::HistogramAggregation`]; count, sum, mean, min, max
+/// and the (population) standard deviation are exact.
 #[derive(Debug, Clone)]
 pub struct HistogramStats {
     pub count: usize,
     pub sum: f64,
     pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stddev: f64,
     pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
     pub p99: f64,
+    pub p999: f64,
+}
+
+/// One time series: a metric name, its attribute set and its value
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Series<T> {
+    pub name: String,
+    pub attributes: MetricAttributes,
+    pub value: T,
+}
+
+impl<T> Series<T> {
+    pub fn new(name: impl Into<String>, attributes: MetricAttributes, value: T) -> Self {
+        Self {
+            name: name.into(),
+            attributes,
+            value,
+        }
+    }
+}
+
+/// Point-in-time copy of every metric, see [`MetricsCollector::collect`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MetricsData {
+    /// Start of every sum and histogram: when the collector was created for
+    /// cumulative data, or the reader's previous delta collection
+    pub start_time_unix_nano: u64,
+    pub time_unix_nano: u64,
+    pub temporality: Temporality,
+    pub counters: Vec<Series<u64>>,
+    pub gauges: Vec<Series<f64>>,
+    pub up_down_counters: Vec<Series<i64>>,
+    pub histograms: Vec<Series<HistogramData>>,
+    /// Histograms configured with [`histogram::HistogramAggregation::Exponential`]
+    pub exponential_histograms: Vec<Series<ExponentialHistogramData>>,
+}
+
+impl MetricsData {
+    pub fn is_empty(&self) -> bool {
+        self.series() == 0
+    }
+
+    /// Number of series, one data point each
+    pub fn series(&self) -> usize {
+        self.counters.len()
+            + self.gauges.len()
+            + self.up_down_counters.len()
+            + self.histograms.len()
+            + self.exponential_histograms.len()
+    }
+}
+
+/// Explicit-bucket histogram point. `bucket_counts` has one more entry than
+/// `bounds`; bucket `i` counts values in `(bounds[i-1], bounds[i]]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HistogramData {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub bounds: Vec<f64>,
+    pub bucket_counts: Vec<u64>,
+}
+
+/// Exponential histogram point in the OpenTelemetry data model. Bucket
+/// `offset + i` counts magnitudes in `(base^(offset+i), base^(offset+i+1)]`
+/// with `base = 2^(2^-scale)`; zeros are counted separately.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExponentialHistogramData {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub scale: i32,
+    pub zero_count: u64,
+    pub positive: ExponentialBuckets,
+    pub negative: ExponentialBuckets,
+}
+
+/// Contiguous bucket counts for one sign of an exponential histogram
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExponentialBuckets {
+    pub offset: i32,
+    pub bucket_counts: Vec<u64>,
+}
+
+impl ExponentialHistogramData {
+    /// Re-bucket onto explicit `bounds` for backends without exponential
+    /// histograms. Each bucket is counted at its upper end, so a bucket
+    /// straddling a bound lands above it.
+    pub fn to_explicit(&self, bounds: &[f64]) -> HistogramData {
+        let mut bucket_counts = vec![0; bounds.len() + 1];
+        let mut add = |upper: f64, count: u64| bucket_counts[bounds.partition_point(|&b| b < upper)] += count;
+        for (i, &count) in self.negative.bucket_counts.iter().enumerate() {
+            add(-histogram::lower_boundary(self.negative.offset + i as i32, self.scale), count);
+        }
+        add(0.0, self.zero_count);
+        for (i, &count) in self.positive.bucket_counts.iter().enumerate() {
+            add(histogram::lower_boundary(self.positive.offset + i as i32 + 1, self.scale), count);
+        }
+        HistogramData {
+            count: self.count,
+            sum: self.sum,
+            min: self.min,
+            max: self.max,
+            bounds: bounds.to_vec(),
+            bucket_counts,
+        }
+    }
 }
 
 /// Telemetry snapshot for export
@@ -157,6 +728,14 @@ pub struct HistogramStats {
 pub struct TelemetrySnapshot {
     pub service: String,
     pub span_count: usize,
+    /// Log records awaiting export
+    pub log_count: usize,
+    /// Spans discarded because the span queue was full
+    pub dropped_spans: u64,
+    /// Log records discarded because the log queue was full
+    pub dropped_logs: u64,
+    /// Traces held by tail sampling awaiting a decision
+    pub pending_traces: usize,
     pub counter_count: usize,
     pub gauge_count: usize,
     pub histogram_count: usize,
@@ -166,28 +745,141 @@ pub struct TelemetrySnapshot {
 impl SigmaTelemetry {
     /// Create a new telemetry instance
     pub fn new(config: TelemetryConfig) -> Self {
+        let sampler = sampling::from_config(&config);
+        Self::with_sampler(config, sampler)
+    }
+
+    /// Create a telemetry instance with a custom head sampler
+    pub fn with_sampler(config: TelemetryConfig, sampler: Box<dyn sampling::Sampler>) -> Self {
+        let active_spans =
+            buffer::SpanQueue::new(config.max_queue_size, config.overflow_policy, config.max_buffer_size);
+        let logs = buffer::LogQueue::new(config.max_queue_size, config.overflow_policy, config.max_buffer_size);
+        let tail_sampler = config.tail_sampling.clone().map(tail_sampling::TailSampler::new);
+        let metrics = MetricsCollector::new(config.histograms.clone());
         Self {
             config,
-            metrics: MetricsCollector::new(),
-            active_spans: std::sync::Mutex::new(Vec::new()),
+            metrics,
+            active_spans,
+            logs,
+            sampler,
+            tail_sampler,
         }
     }
 
     /// Start a new span for tracing
     pub fn start_span(&self, name: &str, operation: SpanOperation) -> SpanGuard<'_> {
+        Self::start_span_in(self, name, operation, SpanOptions::new())
+    }
+
+    /// Start a span with a kind, parent, links or initial attributes, e.g.
+    /// from a [`spans::SpanTemplates`] entry
+    pub fn start_span_with_options(
+        &self,
+        name: &str,
+        operation: SpanOperation,
+        options: SpanOptions,
+    ) -> SpanGuard<'_> {
+        Self::start_span_in(self, name, operation, options)
+    }
+
+    /// Start a root span linked to other spans, e.g. a batched decode step
+    /// pointing back at every request it served
+    pub fn start_span_with_links(
+        &self,
+        name: &str,
+        operation: SpanOperation,
+        links: Vec<SpanLink>,
+    ) -> SpanGuard<'_> {
+        Self::start_span_in(self, name, operation, SpanOptions::new().with_links(links))
+    }
+
+    /// Start a span that continues `parent`, typically a context extracted
+    /// from incoming `traceparent` headers. An invalid parent starts a new trace.
+    pub fn start_span_with_parent(
+        &self,
+        name: &str,
+        operation: SpanOperation,
+        parent: &SpanContext,
+    ) -> SpanGuard<'_> {
+        Self::start_span_in(self, name, operation, SpanOptions::new().with_parent(parent))
+    }
+
+    /// Start a span whose guard owns a handle to this instance, so it can be
+    /// moved into spawned tasks and held across `.await` points
+    pub fn start_owned_span(self: &Arc<Self>, name: &str, operation: SpanOperation) -> OwnedSpanGuard {
+        Self::start_span_in(Arc::clone(self), name, operation, SpanOptions::new())
+    }
+
+    /// Owned counterpart of [`SigmaTelemetry::start_span_with_options`]
+    pub fn start_owned_span_with_options(
+        self: &Arc<Self>,
+        name: &str,
+        operation: SpanOperation,
+        options: SpanOptions,
+    ) -> OwnedSpanGuard {
+        Self::start_span_in(Arc::clone(self), name, operation, options)
+    }
+
+    /// Owned counterpart of [`SigmaTelemetry::start_span_with_links`]
+    pub fn start_owned_span_with_links(
+        self: &Arc<Self>,
+        name: &str,
+        operation: SpanOperation,
+        links: Vec<SpanLink>,
+    ) -> OwnedSpanGuard {
+        Self::start_span_in(Arc::clone(self), name, operation, SpanOptions::new().with_links(links))
+    }
+
+    /// Owned counterpart of [`SigmaTelemetry::start_span_with_parent`]
+    pub fn start_owned_span_with_parent(
+        self: &Arc<Self>,
+        name: &str,
+        operation: SpanOperation,
+        parent: &SpanContext,
+    ) -> OwnedSpanGuard {
+        Self::start_span_in(Arc::clone(self), name, operation, SpanOptions::new().with_parent(parent))
+    }
+
+    /// Start a span under `parent`, or as the root of a new trace
+    fn start_span_in<H: TelemetryHandle>(
+        telemetry: H,
+        name: &str,
+        operation: SpanOperation,
+        options: SpanOptions,
+    ) -> GuardedSpan<H> {
+        let parent = options.parent.as_ref().filter(|p| p.is_valid());
+        let trace_id = parent.map(|p| p.trace_id).unwrap_or_else(TraceId::random);
+        let trace_flags = match parent {
+            Some(p) if !p.is_remote => p.trace_flags,
+            _ => match telemetry.sampler.should_sample(parent, trace_id, name, &operation) {
+                sampling::SamplingDecision::RecordAndSample => TraceFlags::SAMPLED,
+                sampling::SamplingDecision::Drop => TraceFlags::NOT_SAMPLED,
+            },
+        };
+        // Unsampled spans only carry their IDs, for children and propagation
+        let sampled = trace_flags.is_sampled();
         let record = SpanRecord {
-            name: name.to_string(),
-            service: self.config.service_name.clone(),
+            name: if sampled { name.to_string() } else { String::new() },
+            service: if sampled { telemetry.config.service_name.clone() } else { String::new() },
             operation,
+            kind: options.kind,
+            trace_id,
+            span_id: SpanId::random(),
+            parent_span_id: parent.map(|p| p.span_id),
+            trace_state: parent.map(|p| p.trace_state.clone()).unwrap_or_default(),
+            parent_is_remote: parent.is_some_and(|p| p.is_remote),
             start_time: std::time::SystemTime::now(),
             duration: None,
-            attributes: Vec::new(),
+            attributes: if sampled { options.attributes } else { Attributes::new() },
+            events: Vec::new(),
+            links: if sampled { options.links } else { Vec::new() },
             status: SpanStatus::Unset,
         };
-        SpanGuard {
+        GuardedSpan {
             record,
+            trace_flags,
             start: Instant::now(),
-            telemetry: self,
+            telemetry,
         }
     }
 
@@ -206,38 +898,160 @@ impl SigmaTelemetry {
             let key = format!("span.{}.duration_ms", span.operation);
             self.metrics.record_histogram(&key, duration.as_secs_f64() * 1000.0);
         }
-        let mut spans = self.active_spans.lock().unwrap();
-        spans.push(span);
+        match &self.tail_sampler {
+            Some(tail) => self.release(tail.offer(span)),
+            None => self.active_spans.push(span),
+        }
+    }
+
+    /// Queue spans from kept traces and count the tail decisions
+    fn release(&self, outcome: tail_sampling::TailOutcome) {
+        if outcome.kept > 0 {
+            self.metrics.increment_by("traces.tail_kept", outcome.kept);
+        }
+        if outcome.dropped > 0 {
+            self.metrics.increment_by("traces.tail_dropped", outcome.dropped);
+        }
+        for span in outcome.spans {
+            self.active_spans.push(span);
+        }
+    }
+
+    /// Decide tail-sampled traces that have outwaited `decision_wait_secs`,
+    /// or every buffered trace when `all` is set
+    pub fn flush_pending_traces(&self, all: bool) {
+        if let Some(tail) = &self.tail_sampler {
+            self.release(tail.flush(all));
+        }
+    }
+
+    /// Remove up to `max` finished spans, oldest first, for export
+    pub fn drain_spans(&self, max: usize) -> Vec<SpanRecord> {
+        self.active_spans.drain(max)
+    }
+
+    /// Emit a log record outside any span. Use [`GuardedSpan::log`] to
+    /// correlate it with a span.
+    pub fn log(&self, severity: Severity, body: &str, attributes: &[(&str, AttributeValue)]) {
+        let record = LogRecord {
+            attributes: attributes.iter().map(|(k, v)| (*k, v.clone())).collect(),
+            ..LogRecord::new(severity, body)
+        };
+        self.emit_log(record);
+    }
+
+    /// Queue a fully built log record for export
+    pub fn emit_log(&self, record: LogRecord) {
+        self.logs.push(record);
+    }
+
+    /// Remove up to `max` log records, oldest first, for export
+    pub fn drain_logs(&self, max: usize) -> Vec<LogRecord> {
+        self.logs.drain(max)
     }
 
     /// Get telemetry snapshot
     pub fn snapshot(&self) -> TelemetrySnapshot {
-        let spans = self.active_spans.lock().unwrap();
-        let counters = self.metrics.counters.lock().unwrap();
-        let gauges = self.metrics.gauges.lock().unwrap();
-        let histograms = self.metrics.histograms.lock().unwrap();
+        // Collected like an export so observable series are counted
+        let metrics = self.metrics.export_data();
         TelemetrySnapshot {
             service: self.config.service_name.clone(),
-            span_count: spans.len(),
-            counter_count: counters.len(),
-            gauge_count: gauges.len(),
-            histogram_count: histograms.len(),
+            span_count: self.active_spans.len(),
+            log_count: self.logs.len(),
+            dropped_spans: self.active_spans.dropped(),
+            dropped_logs: self.logs.dropped(),
+            pending_traces: self.tail_sampler.as_ref().map_or(0, |t| t.pending_traces()),
+            counter_count: metrics.counters.len(),
+            gauge_count: metrics.gauges.len(),
+            histogram_count: metrics.histograms.len() + metrics.exponential_histograms.len(),
             uptime_secs: 0.0,
         }
     }
 }
 
-/// RAII span guard that records timing on drop
-pub struct SpanGuard<'a> {
+/// Anything a span guard can reach its telemetry instance through
+pub trait TelemetryHandle: Deref<Target = SigmaTelemetry> + Clone {}
+
+impl<T: Deref<Target = SigmaTelemetry> + Clone> TelemetryHandle for T {}
+
+/// RAII span guard that records timing on drop, generic over how it holds
+/// the telemetry instance. Use the [`SpanGuard`] and [`OwnedSpanGuard`] aliases.
+pub struct GuardedSpan<H: TelemetryHandle> {
     record: SpanRecord,
+    trace_flags: TraceFlags,
     start: Instant,
-    telemetry: &'a SigmaTelemetry,
+    telemetry: H,
 }
 
-impl<'a> SpanGuard<'a> {
-    /// Add an attribute to the span
-    pub fn set_attribute(&mut self, key: &str, value: &str) {
-        self.record.attributes.push((key.to_string(), value.to_string()));
+/// Span guard borrowing its telemetry instance
+pub type SpanGuard<'a> = GuardedSpan<&'a SigmaTelemetry>;
+
+/// `Send + 'static` span guard holding an `Arc` to its telemetry instance
+pub type OwnedSpanGuard = GuardedSpan<Arc<SigmaTelemetry>>;
+
+impl<H: TelemetryHandle> GuardedSpan<H> {
+    /// Trace context of this span, for children and propagation
+    pub fn span_context(&self) -> SpanContext {
+        SpanContext {
+            trace_id: self.record.trace_id,
+            span_id: self.record.span_id,
+            trace_flags: self.trace_flags,
+            trace_state: self.record.trace_state.clone(),
+            is_remote: false,
+        }
+    }
+
+    /// Whether this span was sampled and will be recorded
+    pub fn is_recording(&self) -> bool {
+        self.trace_flags.is_sampled()
+    }
+
+    /// Write `traceparent`/`tracestate` for this span into `carrier`
+    pub fn inject_context<I: propagation::Injector + ?Sized>(&self, carrier: &mut I) {
+        propagation::inject(&self.span_context(), carrier);
+    }
+
+    /// Start a child span in the same trace
+    pub fn child_span(&self, name: &str, operation: SpanOperation) -> GuardedSpan<H> {
+        self.child_span_with_kind(name, operation, SpanKind::Internal)
+    }
+
+    /// Start a child span of the given kind, e.g. a `Client` span around an
+    /// outbound call made while serving this span
+    pub fn child_span_with_kind(&self, name: &str, operation: SpanOperation, kind: SpanKind) -> GuardedSpan<H> {
+        let options = SpanOptions::new().with_kind(kind).with_parent(&self.span_context());
+        SigmaTelemetry::start_span_in(self.telemetry.clone(), name, operation, options)
+    }
+
+    /// Set an attribute on the span, replacing any previous value for `key`
+    pub fn set_attribute(&mut self, key: &str, value: impl Into<AttributeValue>) {
+        if !self.is_recording() {
+            return;
+        }
+        self.record.attributes.insert(key, value);
+    }
+
+    /// Record a point-in-time event such as "prefill_done" or "kv_evicted"
+    pub fn add_event(&mut self, name: &str, attributes: &[(&str, AttributeValue)]) {
+        if !self.is_recording() {
+            return;
+        }
+        self.record.events.push(SpanEvent {
+            name: name.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            attributes: attributes.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        });
+    }
+
+    /// Emit a log record carrying this span's trace and span IDs. Logs are
+    /// kept whether or not the span is sampled.
+    pub fn log(&self, severity: Severity, body: &str, attributes: &[(&str, AttributeValue)]) {
+        let record = LogRecord {
+            attributes: attributes.iter().map(|(k, v)| (*k, v.clone())).collect(),
+            ..LogRecord::new(severity, body)
+        }
+        .with_context(&self.span_context());
+        self.telemetry.emit_log(record);
     }
 
     /// Mark span as OK
@@ -248,20 +1062,24 @@ impl<'a> SpanGuard<'a> {
 
     /// Mark span as error
     pub fn set_error(mut self, msg: &str) {
-        self.record.status = SpanStatus::Error(msg.to_string());
+        if self.is_recording() {
+            self.record.status = SpanStatus::Error(msg.to_string());
+        }
         self.finish();
     }
 
     fn finish(mut self) {
         self.record.duration = Some(self.start.elapsed());
-        self.telemetry.record_span(self.record.clone());
-        std::mem::forget(self); // prevent double record on drop
+        if self.is_recording() {
+            self.telemetry.record_span(self.record.clone());
+        }
+        // Drop sees the duration and does not record again
     }
 }
 
-impl<'a> Drop for SpanGuard<'a> {
+impl<H: TelemetryHandle> Drop for GuardedSpan<H> {
     fn drop(&mut self) {
-        if self.record.duration.is_none() {
+        if self.is_recording() && self.record.duration.is_none() {
             self.record.duration = Some(self.start.elapsed());
             self.record.status = SpanStatus::Ok;
             self.telemetry.record_span(self.record.clone());
@@ -313,6 +1131,225 @@ mod tests {
         assert_eq!(t.metrics().get_counter("spans.errors"), 1);
     }
 
+    #[test]
+    fn test_child_span_inherits_trace() {
+        let t = test_telemetry();
+        let root = t.start_span("request", SpanOperation::Inference);
+        let root_ctx = root.span_context();
+        {
+            let token = root.child_span("decode", SpanOperation::TokenGeneration);
+            token.child_span("kv", SpanOperation::KvCacheOp).set_ok();
+            token.set_ok();
+        }
+        root.set_ok();
+
+        let spans = t.active_spans.to_vec();
+        assert_eq!(spans.len(), 3);
+        assert!(spans.iter().all(|s| s.trace_id == root_ctx.trace_id));
+        let (kv, token, root) = (&spans[0], &spans[1], &spans[2]);
+        assert_eq!(root.parent_span_id, None);
+        assert_eq!(token.parent_span_id, Some(root.span_id));
+        assert_eq!(kv.parent_span_id, Some(token.span_id));
+    }
+
+    #[test]
+    fn test_root_spans_get_distinct_traces() {
+        let t = test_telemetry();
+        let a = t.start_span("a", SpanOperation::Inference);
+        let b = t.start_span("b", SpanOperation::Inference);
+        assert_ne!(a.span_context().trace_id, b.span_context().trace_id);
+    }
+
+    #[test]
+    fn test_span_continues_remote_parent() {
+        let t = test_telemetry();
+        let mut headers = std::collections::HashMap::new();
+        headers.insert(
+            "traceparent".to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+        );
+        headers.insert("tracestate".to_string(), "gateway=edge1".to_string());
+        let remote = propagation::extract(&headers).unwrap();
+
+        let span = t.start_span_with_parent("serve", SpanOperation::Inference, &remote);
+        let ctx = span.span_context();
+        assert_eq!(ctx.trace_id, remote.trace_id);
+        assert_eq!(ctx.trace_state.get("gateway"), Some("edge1"));
+        span.set_ok();
+
+        let spans = t.active_spans.to_vec();
+        assert_eq!(spans[0].parent_span_id, Some(remote.span_id));
+        assert_eq!(spans[0].trace_state.header(), "gateway=edge1", "kept for export");
+    }
+
+    #[test]
+    fn test_owned_span_across_spawn() {
+        fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+        let t = Arc::new(test_telemetry());
+        let span = t.start_owned_span("stream", SpanOperation::TokenGeneration);
+        assert_send_static(&span);
+
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async move {
+            tokio::spawn(async move {
+                tokio::task::yield_now().await;
+                span.child_span("kv", SpanOperation::KvCacheOp).set_ok();
+                span.set_ok();
+            })
+            .await
+            .unwrap();
+        });
+
+        let spans = t.active_spans.to_vec();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].parent_span_id, Some(spans[1].span_id));
+    }
+
+    #[test]
+    fn test_typed_attributes_overwrite() {
+        let t = test_telemetry();
+        let mut span = t.start_span("generate", SpanOperation::Inference);
+        span.set_attribute("model", "bitnet-3b");
+        span.set_attribute("tokens", 16);
+        span.set_attribute("tokens", 32);
+        span.set_attribute("streaming", true);
+        span.set_ok();
+
+        let spans = t.active_spans.to_vec();
+        let attrs = &spans[0].attributes;
+        assert_eq!(attrs.len(), 3);
+        assert_eq!(attrs.get("tokens"), Some(&AttributeValue::Int(32)));
+        assert_eq!(attrs.get("streaming"), Some(&AttributeValue::Bool(true)));
+    }
+
+    #[test]
+    fn test_span_events() {
+        let t = test_telemetry();
+        let mut span = t.start_span("generate", SpanOperation::Inference);
+        span.add_event("prefill_done", &[]);
+        span.add_event("draft_rejected", &[("draft.position", 3.into())]);
+        span.set_ok();
+
+        let spans = t.active_spans.to_vec();
+        let events = &spans[0].events;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, "prefill_done");
+        assert_eq!(events[1].attributes.get("draft.position"), Some(&AttributeValue::Int(3)));
+        assert!(events[0].timestamp <= events[1].timestamp);
+        assert!(spans[0].start_time <= events[0].timestamp);
+    }
+
+    #[test]
+    fn test_span_links() {
+        let t = test_telemetry();
+        let req_a = t.start_span("req_a", SpanOperation::Inference);
+        let req_b = t.start_span("req_b", SpanOperation::Inference);
+        let links = vec![
+            SpanLink::new(&req_a.span_context()).with_attribute("batch.slot", "0"),
+            SpanLink::new(&req_b.span_context()).with_attribute("batch.slot", "1"),
+        ];
+        let step = t.start_span_with_links("decode_step", SpanOperation::TokenGeneration, links);
+        let step_trace = step.span_context().trace_id;
+        step.set_ok();
+
+        let spans = t.active_spans.to_vec();
+        let step = &spans[0];
+        assert_ne!(step_trace, req_a.span_context().trace_id);
+        assert_eq!(step.links.len(), 2);
+        assert_eq!(step.links[0].span_id, req_a.span_context().span_id);
+        assert_eq!(step.links[1].trace_id, req_b.span_context().trace_id);
+    }
+
+    #[test]
+    fn test_span_kind_from_options_and_children() {
+        let t = test_telemetry();
+        let (op, options) = spans::SpanTemplates::http_server("POST", "/v1/generate");
+        let server = t.start_span_with_options("POST /v1/generate", op, options);
+        server
+            .child_span_with_kind("health", SpanOperation::Custom("http.client".into()), SpanKind::Client)
+            .set_ok();
+        server.child_span("decode", SpanOperation::TokenGeneration).set_ok();
+        server.set_ok();
+
+        let spans = t.active_spans.to_vec();
+        assert_eq!(spans[0].kind, SpanKind::Client);
+        assert_eq!(spans[1].kind, SpanKind::Internal);
+        assert_eq!(spans[2].kind, SpanKind::Server);
+        assert_eq!(spans[2].attributes.get("http.route").unwrap(), &"/v1/generate");
+        assert_eq!(spans[0].parent_span_id, Some(spans[2].span_id));
+    }
+
+    #[test]
+    fn test_logs_correlated_with_span() {
+        let t = test_telemetry();
+        let span = t.start_span("req", SpanOperation::Inference);
+        let decode = span.child_span("decode", SpanOperation::TokenGeneration);
+        decode.log(Severity::Warn, "logit overflow", &[("token.index", 17.into())]);
+        t.log(Severity::Info, "model warm", &[]);
+
+        let logs = t.drain_logs(usize::MAX);
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].trace_id, Some(span.span_context().trace_id));
+        assert_eq!(logs[0].span_id, Some(decode.span_context().span_id));
+        assert_eq!(logs[0].attributes.get("token.index"), Some(&AttributeValue::Int(17)));
+        assert_eq!((logs[1].severity, logs[1].trace_id), (Severity::Info, None));
+    }
+
+    #[test]
+    fn test_span_and_log_queues_bounded() {
+        let t = SigmaTelemetry::new(TelemetryConfig {
+            max_queue_size: 3,
+            ..TelemetryConfig::default()
+        });
+        for _ in 0..5 {
+            t.start_span("step", SpanOperation::TokenGeneration).set_ok();
+        }
+        for _ in 0..4 {
+            t.log(Severity::Info, "decode step", &[]);
+        }
+        let snap = t.snapshot();
+        assert_eq!(snap.span_count, 3);
+        assert_eq!(snap.dropped_spans, 2);
+        assert_eq!(t.metrics().get_counter("spans.total"), 5);
+        assert_eq!(snap.log_count, 3);
+        assert_eq!(snap.dropped_logs, 1);
+    }
+
+    #[test]
+    fn test_unsampled_spans_not_recorded() {
+        let t = SigmaTelemetry::new(TelemetryConfig {
+            sampling_rate: 0.0,
+            ..TelemetryConfig::default()
+        });
+        let mut root = t.start_span("req", SpanOperation::Inference);
+        root.set_attribute("model", "bitnet");
+        assert!(!root.is_recording());
+        assert!(root.record.attributes.is_empty());
+
+        let child = root.child_span("decode", SpanOperation::TokenGeneration);
+        assert!(!child.is_recording());
+        assert_eq!(child.span_context().trace_id, root.span_context().trace_id);
+        child.set_error("boom");
+        root.set_ok();
+
+        assert_eq!(t.snapshot().span_count, 0);
+        assert_eq!(t.metrics().get_counter("spans.total"), 0);
+    }
+
+    #[test]
+    fn test_local_children_inherit_root_decision() {
+        let t = SigmaTelemetry::with_sampler(TelemetryConfig::default(), Box::new(sampling::AlwaysOn));
+        let mut parent = t.start_span("req", SpanOperation::Inference).span_context();
+        parent.trace_flags = TraceFlags::NOT_SAMPLED;
+        let child = t.start_span_with_parent("decode", SpanOperation::TokenGeneration, &parent);
+        assert!(!child.is_recording());
+
+        parent.is_remote = true;
+        let remote_child = t.start_span_with_parent("serve", SpanOperation::Inference, &parent);
+        assert!(remote_child.is_recording());
+    }
+
     #[test]
     fn test_metrics_counter() {
         let t = test_telemetry();
@@ -340,6 +1377,91 @@ mod tests {
         let stats = t.metrics().get_histogram_stats("latency").unwrap();
         assert_eq!(stats.count, 5);
         assert!((stats.mean - 30.0).abs() < 0.001);
+        assert!((stats.p50 - 30.0).abs() <= 0.3, "p50 {}", stats.p50);
+        assert_eq!((stats.min, stats.max), (10.0, 50.0));
+        assert!((stats.stddev - 200f64.sqrt()).abs() < 1e-9);
+
+        let quantiles = t.metrics().get_histogram_quantiles("latency", &[0.0, 0.75, 1.0]).unwrap();
+        assert_eq!(quantiles[0], 10.0);
+        assert!((quantiles[1] - 40.0).abs() <= 0.4, "p75 {}", quantiles[1]);
+        assert_eq!(quantiles[2], 50.0);
+        assert_eq!(t.metrics().get_histogram_quantiles("missing", &[0.5]), None);
+    }
+
+    #[test]
+    fn test_histogram_aggregation_per_metric() {
+        let mut config = TelemetryConfig::default();
+        config.histograms.overrides.insert(
+            "kv".to_string(),
+            histogram::HistogramAggregation::ExplicitBuckets { bounds: vec![1.0, 2.0] },
+        );
+        let t = SigmaTelemetry::new(config);
+        for _ in 0..100_000 {
+            t.metrics().record_histogram("kv", 1.5);
+            t.metrics().record_histogram("decode", 1.5);
+        }
+        let data = t.metrics().export_data();
+        assert_eq!(data.histograms[1].name, "kv");
+        assert_eq!(data.histograms[1].value.bucket_counts, vec![0, 100_000, 0]);
+        assert_eq!(data.histograms[0].value.bounds, DEFAULT_HISTOGRAM_BOUNDS.to_vec());
+        assert_eq!(t.metrics().get_histogram_stats("decode").unwrap().count, 100_000);
+    }
+
+    #[test]
+    fn test_metrics_export_data() {
+        let mut config = TelemetryConfig::default();
+        config.histograms.overrides.insert(
+            "latency".to_string(),
+            histogram::HistogramAggregation::ExplicitBuckets {
+                bounds: DEFAULT_HISTOGRAM_BOUNDS.to_vec(),
+            },
+        );
+        let t = SigmaTelemetry::new(config);
+        t.metrics().increment("b.requests");
+        t.metrics().increment_by("a.tokens", 7);
+        t.metrics().set_gauge("gpu", 50.0);
+        for v in [0.0, 5.0, 5.5, 20000.0] {
+            t.metrics().record_histogram("latency", v);
+        }
+        let data = t.metrics().export_data();
+        let counters: Vec<_> = data.counters.iter().map(|s| (s.name.as_str(), s.value)).collect();
+        assert_eq!(counters, [("a.tokens", 7), ("b.requests", 1)]);
+        assert!(data.start_time_unix_nano <= data.time_unix_nano);
+
+        let histogram = &data.histograms[0].value;
+        assert_eq!(histogram.bucket_counts.len(), DEFAULT_HISTOGRAM_BOUNDS.len() + 1);
+        // Upper bounds are inclusive: 0 falls in (-inf, 0], 5 in (0, 5]
+        assert_eq!(&histogram.bucket_counts[..3], &[1, 1, 1]);
+        assert_eq!(histogram.bucket_counts[DEFAULT_HISTOGRAM_BOUNDS.len()], 1);
+        assert_eq!((histogram.min, histogram.max, histogram.count), (0.0, 20000.0, 4));
+    }
+
+    #[test]
+    fn test_dimensional_metrics() {
+        let t = test_telemetry();
+        let m = t.metrics();
+        m.increment_with("requests", &[("model", "bitnet-3b"), ("tenant", "acme")]);
+        m.increment_by_with("requests", 2, &[("tenant", "acme"), ("model", "bitnet-3b")]);
+        m.increment_with("requests", &[("model", "mamba-2.8b")]);
+        m.increment("requests");
+        m.set_gauge_with("kv_cache.used", 0.5, &[("model", "bitnet-3b")]);
+        m.record_histogram_with("latency", 12.0, &[("model", "bitnet-3b")]);
+        m.record_histogram_with("latency", 80.0, &[("model", "mamba-2.8b")]);
+
+        assert_eq!(m.get_counter_with("requests", &[("tenant", "acme"), ("model", "bitnet-3b")]), 3);
+        assert_eq!(m.get_counter_with("requests", &[("model", "bitnet-3b")]), 0);
+        assert_eq!(m.get_counter("requests"), 1);
+        assert_eq!(m.get_counter_total("requests"), 5);
+        assert_eq!(m.get_gauge_with("kv_cache.used", &[("model", "bitnet-3b")]), Some(0.5));
+        assert_eq!(m.get_gauge("kv_cache.used"), None);
+        assert_eq!(m.get_histogram_stats_with("latency", &[("model", "mamba-2.8b")]).unwrap().max, 80.0);
+        assert!(m.get_histogram_stats("latency").is_none());
+
+        let data = m.export_data();
+        let requests: Vec<_> = data.counters.iter().map(|s| (s.attributes.get("model"), s.value)).collect();
+        assert_eq!(requests, [(None, 1), (Some("bitnet-3b"), 3), (Some("mamba-2.8b"), 1)]);
+        assert_eq!(data.histograms.len(), 2);
+        assert_eq!(t.snapshot().counter_count, 3);
     }
 
     #[test]
@@ -361,5 +1483,26 @@ mod tests {
         assert_eq!(snap.span_count, 2);
         assert!(snap.counter_count >= 1);
         assert_eq!(snap.gauge_count, 1);
+
+        t.metrics().observe_gauge("queue.depth", |o| {
+            o.observe_with(1.0, &[("queue", "decode")]);
+            o.observe_with(2.0, &[("queue", "prefill")]);
+        });
+        assert_eq!(t.snapshot().gauge_count, 3, "observable series are counted");
+    }
+
+    #[test]
+    fn test_observable_sharing_synchronous_name() {
+        let t = test_telemetry();
+        let m = t.metrics();
+        m.increment_by("requests", 5);
+        m.observe_counter("requests", |o| {
+            o.observe(9);
+            o.observe_with(2, &[("model", "bitnet-3b")]);
+        });
+        let data = m.export_data();
+        let requests: Vec<_> = data.counters.iter().map(|s| (s.attributes.get("model"), s.value)).collect();
+        assert_eq!(requests, [(None, 5), (Some("bitnet-3b"), 2)], "one series per attribute set");
+        assert_eq!(t.snapshot().counter_count, 2);
     }
 }
@@ -0,0 +1,120 @@
+//! Structured log records correlated with spans.
+
+use std::fmt;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AttributeValue, Attributes, SpanContext, SpanId, TraceFlags, TraceId};
+
+/// Log severity, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Severity {
+    /// OTLP `SeverityNumber` for the first level of this range, e.g. WARN = 13
+    pub fn number(self) -> i32 {
+        match self {
+            Severity::Trace => 1,
+            Severity::Debug => 5,
+            Severity::Info => 9,
+            Severity::Warn => 13,
+            Severity::Error => 17,
+            Severity::Fatal => 21,
+        }
+    }
+
+    /// Short name used as the OTLP severity text
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Trace => "TRACE",
+            Severity::Debug => "DEBUG",
+            Severity::Info => "INFO",
+            Severity::Warn => "WARN",
+            Severity::Error => "ERROR",
+            Severity::Fatal => "FATAL",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A log record, optionally tied to the span that was active when it was
+/// emitted
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub timestamp: SystemTime,
+    pub severity: Severity,
+    pub body: String,
+    pub attributes: Attributes,
+    /// `None` for logs emitted outside any span
+    pub trace_id: Option<TraceId>,
+    pub span_id: Option<SpanId>,
+    pub trace_flags: TraceFlags,
+}
+
+impl LogRecord {
+    pub fn new(severity: Severity, body: impl Into<String>) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            severity,
+            body: body.into(),
+            attributes: Attributes::new(),
+            trace_id: None,
+            span_id: None,
+            trace_flags: TraceFlags::NOT_SAMPLED,
+        }
+    }
+
+    pub fn with_attribute(mut self, key: &str, value: impl Into<AttributeValue>) -> Self {
+        self.attributes.insert(key, value);
+        self
+    }
+
+    /// Correlate the record with `context`; an invalid context is ignored
+    pub fn with_context(mut self, context: &SpanContext) -> Self {
+        if context.is_valid() {
+            self.trace_id = Some(context.trace_id);
+            self.span_id = Some(context.span_id);
+            self.trace_flags = context.trace_flags;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_numbers_and_order() {
+        assert_eq!(Severity::Warn.number(), 13);
+        assert_eq!(Severity::Fatal.to_string(), "FATAL");
+        assert!(Severity::Debug < Severity::Info && Severity::Error > Severity::Warn);
+    }
+
+    #[test]
+    fn test_with_context() {
+        let context = SpanContext::new(TraceId::random(), SpanId::random());
+        let record = LogRecord::new(Severity::Warn, "nan logits").with_context(&context);
+        assert_eq!(record.trace_id, Some(context.trace_id));
+        assert_eq!(record.span_id, Some(context.span_id));
+        assert!(record.trace_flags.is_sampled());
+
+        let invalid = SpanContext::new(TraceId::INVALID, SpanId::INVALID);
+        assert_eq!(LogRecord::new(Severity::Info, "x").with_context(&invalid).trace_id, None);
+    }
+}
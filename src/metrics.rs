@@ -1,4 +1,11 @@
-//! Well-known metrics for Ryzanstein observability.
+//! Well-known metrics for Ryzanstein observability, and pre-registered
+//! instrument handles for recording them on hot paths.
+
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::histogram::ShardedHistogram;
+use crate::{MetricAttributes, MetricsData, Series};
 
 /// Standard metric names
 pub struct MetricNames;
@@ -33,6 +40,185 @@ impl MetricNames {
     pub const THROUGHPUT_TPS: &'static str = "ryzanstein.system.throughput_tps";
 }
 
+/// Counter series handle from [`crate::MetricsCollector::counter`]. Cheap to
+/// clone; every clone adds to the same series.
+#[derive(Debug, Clone)]
+pub struct Counter {
+    value: Arc<AtomicU64>,
+}
+
+impl Counter {
+    pub(crate) fn new(value: Arc<AtomicU64>) -> Self {
+        Self { value }
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Gauge series handle from [`crate::MetricsCollector::gauge`]
+#[derive(Debug, Clone)]
+pub struct Gauge {
+    cell: Arc<GaugeCell>,
+}
+
+impl Gauge {
+    pub(crate) fn new(cell: Arc<GaugeCell>) -> Self {
+        Self { cell }
+    }
+
+    pub fn set(&self, value: f64) {
+        self.cell.set(value);
+    }
+
+    /// Last value set, `None` before the first
+    pub fn get(&self) -> Option<f64> {
+        self.cell.get()
+    }
+}
+
+/// A gauge series' value. Whether it has been set is kept apart from the
+/// value so that any `f64`, NaN included, can be set.
+#[derive(Debug, Default)]
+pub(crate) struct GaugeCell {
+    bits: AtomicU64,
+    set: AtomicBool,
+}
+
+impl GaugeCell {
+    pub(crate) fn set(&self, value: f64) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+        self.set.store(true, Ordering::Release);
+    }
+
+    /// Last value set, `None` before the first
+    pub(crate) fn get(&self) -> Option<f64> {
+        self.set
+            .load(Ordering::Acquire)
+            .then(|| f64::from_bits(self.bits.load(Ordering::Relaxed)))
+    }
+}
+
+/// Histogram series handle from [`crate::MetricsCollector::histogram`]
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    cells: Arc<ShardedHistogram>,
+}
+
+impl Histogram {
+    pub(crate) fn new(cells: Arc<ShardedHistogram>) -> Self {
+        Self { cells }
+    }
+
+    pub fn record(&self, value: f64) {
+        self.cells.record(value);
+    }
+}
+
+/// Up-down counter series handle from
+/// [`crate::MetricsCollector::up_down_counter`]
+#[derive(Debug, Clone)]
+pub struct UpDownCounter {
+    value: Arc<AtomicI64>,
+}
+
+impl UpDownCounter {
+    pub(crate) fn new(value: Arc<AtomicI64>) -> Self {
+        Self { value }
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn decrement(&self) {
+        self.add(-1);
+    }
+
+    pub fn add(&self, delta: i64) {
+        self.value.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Identifies an observable instrument's callback, for
+/// [`crate::MetricsCollector::remove_callback`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallbackId(pub(crate) u64);
+
+/// Collects the values an observable instrument's callback reports
+pub struct Observer<T> {
+    observations: Vec<(MetricAttributes, T)>,
+}
+
+impl<T> Observer<T> {
+    /// Report the series without attributes
+    pub fn observe(&mut self, value: T) {
+        self.observe_with(value, &[]);
+    }
+
+    /// Report the series for `attributes`; observing it again in the same
+    /// callback replaces the value
+    pub fn observe_with(&mut self, value: T, attributes: &[(&str, &str)]) {
+        let attributes = MetricAttributes::new(attributes);
+        match self.observations.iter_mut().find(|(a, _)| *a == attributes) {
+            Some((_, existing)) => *existing = value,
+            None => self.observations.push((attributes, value)),
+        }
+    }
+}
+
+type ObserveFn<T> = Box<dyn Fn(&mut Observer<T>) + Send + Sync>;
+
+pub(crate) enum Callback {
+    Counter(ObserveFn<u64>),
+    Gauge(ObserveFn<f64>),
+    UpDownCounter(ObserveFn<i64>),
+}
+
+/// An instrument whose values come from a callback run at collection time
+pub(crate) struct ObservableInstrument {
+    pub(crate) id: CallbackId,
+    name: String,
+    callback: Callback,
+}
+
+impl ObservableInstrument {
+    pub(crate) fn new(id: CallbackId, name: &str, callback: Callback) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            callback,
+        }
+    }
+
+    /// Run the callback, appending what it reports to `data`
+    pub(crate) fn observe(&self, data: &mut MetricsData) {
+        fn run<T>(name: &str, callback: &ObserveFn<T>, out: &mut Vec<Series<T>>) {
+            let mut observer = Observer { observations: Vec::new() };
+            callback(&mut observer);
+            out.extend(observer.observations.into_iter().map(|(a, v)| Series::new(name, a, v)));
+        }
+        match &self.callback {
+            Callback::Counter(f) => run(&self.name, f, &mut data.counters),
+            Callback::Gauge(f) => run(&self.name, f, &mut data.gauges),
+            Callback::UpDownCounter(f) => run(&self.name, f, &mut data.up_down_counters),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,7 +231,7 @@ mod tests {
 
     #[test]
     fn test_metric_names_unique() {
-        let names = vec![
+        let names = [
             MetricNames::INFERENCE_REQUESTS,
             MetricNames::INFERENCE_TOKENS,
             MetricNames::INFERENCE_LATENCY_MS,
@@ -58,4 +244,85 @@ mod tests {
         let unique: std::collections::HashSet<_> = names.iter().collect();
         assert_eq!(names.len(), unique.len());
     }
+
+    #[test]
+    fn test_handles_share_series_with_collector() {
+        let t = crate::SigmaTelemetry::new(crate::config::TelemetryConfig::default());
+        let m = t.metrics();
+        let tokens = m.counter_with(MetricNames::INFERENCE_TOKENS, &[("model", "bitnet-3b")]);
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let tokens = tokens.clone();
+                std::thread::spawn(move || (0..10_000).for_each(|_| tokens.increment()))
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        m.increment_by_with(MetricNames::INFERENCE_TOKENS, 5, &[("model", "bitnet-3b")]);
+        assert_eq!(tokens.get(), 80_005);
+        assert_eq!(m.get_counter_with(MetricNames::INFERENCE_TOKENS, &[("model", "bitnet-3b")]), 80_005);
+
+        let gpu = m.gauge(MetricNames::GPU_UTILIZATION);
+        assert_eq!((gpu.get(), m.get_gauge(MetricNames::GPU_UTILIZATION)), (None, None));
+        assert!(m.export_data().gauges.is_empty(), "unset gauges are not exported");
+        gpu.set(85.0);
+        assert_eq!(m.get_gauge(MetricNames::GPU_UTILIZATION), Some(85.0));
+        gpu.set(f64::NAN);
+        assert!(gpu.get().is_some_and(f64::is_nan), "NaN is a value, not unset");
+        assert!(m.export_data().gauges[0].value.is_nan());
+
+        let latency = m.histogram(MetricNames::INFERENCE_LATENCY_MS);
+        latency.record(12.0);
+        m.record_histogram(MetricNames::INFERENCE_LATENCY_MS, 30.0);
+        let stats = m.get_histogram_stats(MetricNames::INFERENCE_LATENCY_MS).unwrap();
+        assert_eq!((stats.count, stats.min, stats.max), (2, 12.0, 30.0));
+    }
+
+    #[test]
+    fn test_up_down_counter() {
+        let t = crate::SigmaTelemetry::new(crate::config::TelemetryConfig::default());
+        let m = t.metrics();
+        let active = m.up_down_counter_with("ryzanstein.inference.active_sequences", &[("model", "bitnet-3b")]);
+        active.increment();
+        active.increment();
+        active.decrement();
+        m.add_up_down_with("ryzanstein.inference.active_sequences", -3, &[("model", "bitnet-3b")]);
+        assert_eq!(active.get(), -2);
+        assert_eq!(m.get_up_down_counter_with("ryzanstein.inference.active_sequences", &[("model", "bitnet-3b")]), -2);
+        assert_eq!(m.export_data().up_down_counters[0].value, -2);
+    }
+
+    #[test]
+    fn test_observable_callbacks_run_at_collection() {
+        let t = crate::SigmaTelemetry::new(crate::config::TelemetryConfig::default());
+        let m = t.metrics();
+        let queued = Arc::new(AtomicI64::new(3));
+        let calls = Arc::new(AtomicU64::new(0));
+        let id = {
+            let (queued, calls) = (Arc::clone(&queued), Arc::clone(&calls));
+            m.observe_up_down_counter("ryzanstein.queue.depth", move |o| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                o.observe_with(queued.load(Ordering::Relaxed), &[("queue", "decode")]);
+            })
+        };
+        m.observe_gauge(MetricNames::MEMORY_USAGE_MB, |o| o.observe(512.0));
+        m.observe_counter(MetricNames::KV_CACHE_EVICTIONS, |o| {
+            o.observe(1);
+            o.observe(7);
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 0, "callbacks only run at collection");
+
+        let data = m.export_data();
+        assert_eq!(data.up_down_counters[0].value, 3);
+        assert_eq!(data.up_down_counters[0].attributes.get("queue"), Some("decode"));
+        assert_eq!(data.gauges[0].value, 512.0);
+        assert_eq!(data.counters.len(), 1);
+        assert_eq!(data.counters[0].value, 7, "the last observation of a series wins");
+
+        queued.store(-1, Ordering::Relaxed);
+        assert_eq!(m.export_data().up_down_counters[0].value, -1);
+        assert!(m.remove_callback(id));
+        assert!(!m.remove_callback(id));
+        assert!(m.export_data().up_down_counters.is_empty());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
 }
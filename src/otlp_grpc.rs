@@ -0,0 +1,418 @@
+//! Native OTLP/gRPC export via the collector `TraceService`,
+//! `MetricsService` and `LogsService`.
+
+use std::time::Duration;
+
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_client::LogsServiceClient;
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_client::MetricsServiceClient;
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_client::TraceServiceClient;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::config::TelemetryConfig;
+use crate::error::TelemetryError;
+use crate::otlp_proto;
+use crate::{LogRecord, MetricsData, SpanRecord};
+
+/// Default deadline for connecting and for each export call
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Async OTLP/gRPC span, metrics and log exporter.
+///
+/// Connects to `otlp_endpoint` lazily on first export and reuses the channel.
+pub struct OtlpGrpcExporter {
+    endpoint: String,
+    service_name: String,
+    timeout: Duration,
+    channel: tokio::sync::Mutex<Option<Channel>>,
+}
+
+impl OtlpGrpcExporter {
+    pub fn new(config: &TelemetryConfig) -> Self {
+        Self {
+            endpoint: config.otlp_endpoint.clone(),
+            service_name: config.service_name.clone(),
+            timeout: DEFAULT_TIMEOUT,
+            channel: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Set the connect and per-export deadline
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Export spans, returning how many the collector accepted.
+    ///
+    /// A partial success (some spans rejected) is not an error: the rejected
+    /// count is subtracted and the collector's message is logged as a warning.
+    pub async fn export(&self, spans: &[SpanRecord]) -> Result<usize, TelemetryError> {
+        if spans.is_empty() {
+            return Ok(0);
+        }
+        let mut client = TraceServiceClient::new(self.channel().await?);
+        let mut request = tonic::Request::new(otlp_proto::trace_request(&self.service_name, spans));
+        request.set_timeout(self.timeout);
+
+        let response = self.call(client.export(request)).await?;
+        let rejected = response.partial_success.as_ref().map_or(0, |p| p.rejected_spans.max(0) as usize);
+        if let Some(partial) = response.partial_success.filter(|p| p.rejected_spans > 0 || !p.error_message.is_empty()) {
+            tracing::warn!(
+                rejected_spans = partial.rejected_spans,
+                message = %partial.error_message,
+                "OTLP collector partially accepted span export"
+            );
+        }
+        Ok(spans.len().saturating_sub(rejected))
+    }
+
+    /// Export a metrics snapshot, returning how many data points the
+    /// collector accepted. Partial success is handled as for spans.
+    pub async fn export_metrics(&self, data: &MetricsData) -> Result<usize, TelemetryError> {
+        let points = data.series();
+        if points == 0 {
+            return Ok(0);
+        }
+        let mut client = MetricsServiceClient::new(self.channel().await?);
+        let mut request = tonic::Request::new(otlp_proto::metrics_request(&self.service_name, data));
+        request.set_timeout(self.timeout);
+
+        let response = self.call(client.export(request)).await?;
+        let rejected = response.partial_success.as_ref().map_or(0, |p| p.rejected_data_points.max(0) as usize);
+        if let Some(partial) = response.partial_success.filter(|p| p.rejected_data_points > 0 || !p.error_message.is_empty()) {
+            tracing::warn!(
+                rejected_data_points = partial.rejected_data_points,
+                message = %partial.error_message,
+                "OTLP collector partially accepted metrics export"
+            );
+        }
+        Ok(points.saturating_sub(rejected))
+    }
+
+    /// Export log records, returning how many the collector accepted.
+    /// Partial success is handled as for spans.
+    pub async fn export_logs(&self, logs: &[LogRecord]) -> Result<usize, TelemetryError> {
+        if logs.is_empty() {
+            return Ok(0);
+        }
+        let mut client = LogsServiceClient::new(self.channel().await?);
+        let mut request = tonic::Request::new(otlp_proto::logs_request(&self.service_name, logs));
+        request.set_timeout(self.timeout);
+
+        let response = self.call(client.export(request)).await?;
+        let rejected = response.partial_success.as_ref().map_or(0, |p| p.rejected_log_records.max(0) as usize);
+        if let Some(partial) = response.partial_success.filter(|p| p.rejected_log_records > 0 || !p.error_message.is_empty()) {
+            tracing::warn!(
+                rejected_log_records = partial.rejected_log_records,
+                message = %partial.error_message,
+                "OTLP collector partially accepted log export"
+            );
+        }
+        Ok(logs.len().saturating_sub(rejected))
+    }
+
+    /// Await an export RPC under the configured deadline
+    async fn call<T>(
+        &self,
+        rpc: impl std::future::Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+    ) -> Result<T, TelemetryError> {
+        match tokio::time::timeout(self.timeout, rpc).await {
+            Ok(Ok(response)) => Ok(response.into_inner()),
+            Ok(Err(status)) => Err(TelemetryError::ExportError(format!(
+                "OTLP/gRPC export to {} failed: {}: {}",
+                self.endpoint,
+                status.code(),
+                status.message()
+            ))),
+            Err(_) => Err(TelemetryError::ExportError(format!(
+                "OTLP/gRPC export to {} timed out after {:?}",
+                self.endpoint, self.timeout
+            ))),
+        }
+    }
+
+    async fn channel(&self) -> Result<Channel, TelemetryError> {
+        let mut slot = self.channel.lock().await;
+        if let Some(channel) = slot.as_ref() {
+            return Ok(channel.clone());
+        }
+        let channel = Endpoint::from_shared(self.endpoint.clone())
+            .map_err(|e| TelemetryError::ConfigError(format!("invalid OTLP endpoint {}: {}", self.endpoint, e)))?
+            .connect_timeout(self.timeout)
+            .timeout(self.timeout)
+            .connect()
+            .await
+            .map_err(|e| TelemetryError::ExportError(format!("Failed to reach OTLP endpoint {}: {}", self.endpoint, e)))?;
+        *slot = Some(channel.clone());
+        Ok(channel)
+    }
+}
+
+/// [`OtlpGrpcExporter`] for synchronous code, driven by a private
+/// one-worker runtime that lives as long as it does so the channel, which is
+/// bound to that runtime, is reused across exports. Its methods must not be
+/// called from within an async context.
+pub(crate) struct BlockingGrpcExporter {
+    /// Always `Some` until dropped
+    runtime: Option<tokio::runtime::Runtime>,
+    exporter: OtlpGrpcExporter,
+}
+
+impl BlockingGrpcExporter {
+    pub(crate) fn new(config: &TelemetryConfig) -> Result<Self, TelemetryError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otlp-grpc")
+            .enable_all()
+            .build()
+            .map_err(TelemetryError::IoError)?;
+        Ok(Self {
+            runtime: Some(runtime),
+            exporter: OtlpGrpcExporter::new(config),
+        })
+    }
+
+    pub(crate) fn export(&self, spans: &[SpanRecord]) -> Result<usize, TelemetryError> {
+        self.block_on(self.exporter.export(spans))
+    }
+
+    pub(crate) fn export_metrics(&self, data: &MetricsData) -> Result<usize, TelemetryError> {
+        self.block_on(self.exporter.export_metrics(data))
+    }
+
+    pub(crate) fn export_logs(&self, logs: &[LogRecord]) -> Result<usize, TelemetryError> {
+        self.block_on(self.exporter.export_logs(logs))
+    }
+
+    fn block_on<T>(&self, future: impl std::future::Future<Output = T>) -> T {
+        self.runtime.as_ref().expect("runtime lives until drop").block_on(future)
+    }
+}
+
+impl Drop for BlockingGrpcExporter {
+    /// The owning [`crate::exporter::Exporter`] is often dropped on an async
+    /// task, where shutting a runtime down by blocking would panic
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SigmaTelemetry, SpanOperation};
+    use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{LogsService, LogsServiceServer};
+    use opentelemetry_proto::tonic::collector::logs::v1::{ExportLogsServiceRequest, ExportLogsServiceResponse};
+    use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{
+        MetricsService, MetricsServiceServer,
+    };
+    use opentelemetry_proto::tonic::collector::metrics::v1::{
+        ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+    };
+    use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{TraceService, TraceServiceServer};
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        ExportTracePartialSuccess, ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// In-process stand-in for an OpenTelemetry collector
+    #[derive(Clone, Default)]
+    struct StandInCollector {
+        received: Arc<Mutex<Vec<ExportTraceServiceRequest>>>,
+        received_metrics: Arc<Mutex<Vec<ExportMetricsServiceRequest>>>,
+        received_logs: Arc<Mutex<Vec<ExportLogsServiceRequest>>>,
+        reject: i64,
+        delay: Duration,
+        connections: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[tonic::async_trait]
+    impl TraceService for StandInCollector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            tokio::time::sleep(self.delay).await;
+            self.received.lock().unwrap().push(request.into_inner());
+            Ok(tonic::Response::new(ExportTraceServiceResponse {
+                partial_success: (self.reject > 0).then(|| ExportTracePartialSuccess {
+                    rejected_spans: self.reject,
+                    error_message: "span name too long".to_string(),
+                }),
+            }))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl MetricsService for StandInCollector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportMetricsServiceRequest>,
+        ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+            self.received_metrics.lock().unwrap().push(request.into_inner());
+            Ok(tonic::Response::new(ExportMetricsServiceResponse { partial_success: None }))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl LogsService for StandInCollector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportLogsServiceRequest>,
+        ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+            self.received_logs.lock().unwrap().push(request.into_inner());
+            Ok(tonic::Response::new(ExportLogsServiceResponse { partial_success: None }))
+        }
+    }
+
+    async fn serve(collector: StandInCollector) -> TelemetryConfig {
+        use tokio_stream::StreamExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::clone(&collector.connections);
+        let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener)
+            .map(move |stream| {
+                connections.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                stream
+            });
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(collector.clone()))
+                .add_service(MetricsServiceServer::new(collector.clone()))
+                .add_service(LogsServiceServer::new(collector))
+                .serve_with_incoming(incoming),
+        );
+        TelemetryConfig {
+            otlp_endpoint: format!("http://{}", addr),
+            ..TelemetryConfig::default()
+        }
+    }
+
+    fn spans(n: usize) -> Vec<SpanRecord> {
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        for _ in 0..n {
+            t.start_span("decode", SpanOperation::TokenGeneration).set_ok();
+        }
+        t.drain_spans(n)
+    }
+
+    #[tokio::test]
+    async fn test_export_reaches_collector() {
+        let collector = StandInCollector::default();
+        let config = serve(collector.clone()).await;
+        let spans = spans(3);
+
+        let accepted = OtlpGrpcExporter::new(&config).export(&spans).await.unwrap();
+        assert_eq!(accepted, 3);
+        let received = collector.received.lock().unwrap();
+        let exported = &received[0].resource_spans[0].scope_spans[0].spans;
+        assert_eq!(exported.len(), 3);
+        assert_eq!(exported[0].trace_id, spans[0].trace_id.to_bytes().to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_share_channel_with_traces() {
+        let collector = StandInCollector::default();
+        let config = serve(collector.clone()).await;
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        t.metrics().increment("requests");
+        t.metrics().record_histogram("latency_ms", 3.0);
+
+        let exporter = OtlpGrpcExporter::new(&config);
+        exporter.export(&spans(1)).await.unwrap();
+        assert_eq!(exporter.export_metrics(&t.metrics().export_data()).await.unwrap(), 2);
+        let received = collector.received_metrics.lock().unwrap();
+        assert_eq!(received[0].resource_metrics[0].scope_metrics[0].metrics.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_logs_export() {
+        let collector = StandInCollector::default();
+        let config = serve(collector.clone()).await;
+        let logs = vec![crate::LogRecord::new(crate::Severity::Warn, "slow decode")];
+
+        assert_eq!(OtlpGrpcExporter::new(&config).export_logs(&logs).await.unwrap(), 1);
+        let received = collector.received_logs.lock().unwrap();
+        assert_eq!(received[0].resource_logs[0].scope_logs[0].log_records[0].severity_text, "WARN");
+    }
+
+    #[tokio::test]
+    async fn test_partial_success() {
+        let config = serve(StandInCollector {
+            reject: 1,
+            ..StandInCollector::default()
+        })
+        .await;
+        let accepted = OtlpGrpcExporter::new(&config).export(&spans(4)).await.unwrap();
+        assert_eq!(accepted, 3);
+    }
+
+    #[tokio::test]
+    async fn test_export_timeout() {
+        let config = serve(StandInCollector {
+            delay: Duration::from_secs(5),
+            ..StandInCollector::default()
+        })
+        .await;
+        let err = OtlpGrpcExporter::new(&config)
+            .with_timeout(Duration::from_millis(100))
+            .export(&spans(1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("OTLP/gRPC"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_collector() {
+        let config = TelemetryConfig {
+            otlp_endpoint: "http://127.0.0.1:1".to_string(),
+            ..TelemetryConfig::default()
+        };
+        let err = OtlpGrpcExporter::new(&config).export(&spans(1)).await.unwrap_err();
+        assert!(err.to_string().contains("127.0.0.1:1"), "{err}");
+    }
+
+    #[test]
+    fn test_blocking_export_through_exporter() {
+        use crate::exporter::{ExportFormat, Exporter};
+
+        let collector = StandInCollector::default();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let config = runtime.block_on(serve(collector.clone()));
+
+        let result = Exporter::new(config, ExportFormat::OtlpGrpc).export(&spans(2)).unwrap();
+        assert!(result.contains("Exported 2 spans"), "{result}");
+        assert_eq!(collector.received.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_blocking_exports_reuse_connection() {
+        use crate::exporter::{ExportFormat, Exporter};
+
+        let collector = StandInCollector::default();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let config = runtime.block_on(serve(collector.clone()));
+
+        let exporter = Exporter::new(config, ExportFormat::OtlpGrpc);
+        for _ in 0..3 {
+            exporter.export(&spans(1)).unwrap();
+        }
+        exporter.export_logs(&[crate::LogRecord::new(crate::Severity::Info, "warm")]).unwrap();
+        assert_eq!(collector.received.lock().unwrap().len(), 3);
+        assert_eq!(collector.connections.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exporter_dropped_on_async_task() {
+        use crate::exporter::{ExportFormat, Exporter};
+
+        let config = serve(StandInCollector::default()).await;
+        let exporter = std::sync::Arc::new(Exporter::new(config, ExportFormat::OtlpGrpc));
+        let blocking = std::sync::Arc::clone(&exporter);
+        tokio::task::spawn_blocking(move || blocking.export(&spans(1)).unwrap()).await.unwrap();
+        drop(exporter);
+    }
+}
@@ -0,0 +1,409 @@
+//! Conversion of span records, metrics and logs into OTLP protobuf messages.
+
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, ArrayValue, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::logs::v1::{ResourceLogs, ScopeLogs};
+use opentelemetry_proto::tonic::metrics::v1::{
+    exponential_histogram_data_point, metric, number_data_point, AggregationTemporality, ExponentialHistogram,
+    ExponentialHistogramDataPoint, Gauge, Histogram, HistogramDataPoint, Metric, NumberDataPoint, ResourceMetrics,
+    ScopeMetrics, Sum,
+};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::{span, status, ResourceSpans, ScopeSpans, Span, Status};
+
+use crate::exporter::unix_nanos;
+use crate::logs::LogRecord;
+use crate::{
+    AttributeValue, Attributes, ExponentialBuckets, MetricAttributes, MetricsData, Series, SpanKind, SpanRecord,
+    SpanStatus, Temporality,
+};
+
+/// Build an `ExportTraceServiceRequest` carrying `spans` under one resource
+pub fn trace_request(service_name: &str, spans: &[SpanRecord]) -> ExportTraceServiceRequest {
+    ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: Some(resource(service_name)),
+            scope_spans: vec![ScopeSpans {
+                scope: Some(scope()),
+                spans: spans.iter().map(to_proto_span).collect(),
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+/// Build an `ExportMetricsServiceRequest` with the same resource and scope as
+/// [`trace_request`]. Series sharing a name become data points of one metric.
+pub fn metrics_request(service_name: &str, data: &MetricsData) -> ExportMetricsServiceRequest {
+    let temporality = match data.temporality {
+        Temporality::Cumulative => AggregationTemporality::Cumulative,
+        Temporality::Delta => AggregationTemporality::Delta,
+    } as i32;
+    let number = |attributes: &MetricAttributes, value: number_data_point::Value, start_time_unix_nano: u64| NumberDataPoint {
+        attributes: key_values(&attributes.to_attributes()),
+        start_time_unix_nano,
+        time_unix_nano: data.time_unix_nano,
+        value: Some(value),
+        ..Default::default()
+    };
+
+    let counters = metrics(&data.counters, |series| {
+        metric::Data::Sum(Sum {
+            data_points: series
+                .iter()
+                .map(|s| {
+                    let value = number_data_point::Value::AsInt(i64::try_from(s.value).unwrap_or(i64::MAX));
+                    number(&s.attributes, value, data.start_time_unix_nano)
+                })
+                .collect(),
+            aggregation_temporality: temporality,
+            is_monotonic: true,
+        })
+    });
+    let up_down_counters = metrics(&data.up_down_counters, |series| {
+        metric::Data::Sum(Sum {
+            data_points: series
+                .iter()
+                .map(|s| number(&s.attributes, number_data_point::Value::AsInt(s.value), data.start_time_unix_nano))
+                .collect(),
+            aggregation_temporality: temporality,
+            is_monotonic: false,
+        })
+    });
+    let gauges = metrics(&data.gauges, |series| {
+        metric::Data::Gauge(Gauge {
+            data_points: series
+                .iter()
+                .map(|s| number(&s.attributes, number_data_point::Value::AsDouble(s.value), 0))
+                .collect(),
+        })
+    });
+    let histograms = metrics(&data.histograms, |series| {
+        metric::Data::Histogram(Histogram {
+            data_points: series
+                .iter()
+                .map(|s| HistogramDataPoint {
+                    attributes: key_values(&s.attributes.to_attributes()),
+                    start_time_unix_nano: data.start_time_unix_nano,
+                    time_unix_nano: data.time_unix_nano,
+                    count: s.value.count,
+                    sum: Some(s.value.sum),
+                    bucket_counts: s.value.bucket_counts.clone(),
+                    explicit_bounds: s.value.bounds.clone(),
+                    min: Some(s.value.min),
+                    max: Some(s.value.max),
+                    ..Default::default()
+                })
+                .collect(),
+            aggregation_temporality: temporality,
+        })
+    });
+
+    let buckets = |b: &ExponentialBuckets| exponential_histogram_data_point::Buckets {
+        offset: b.offset,
+        bucket_counts: b.bucket_counts.clone(),
+    };
+    let exponential_histograms = metrics(&data.exponential_histograms, |series| {
+        metric::Data::ExponentialHistogram(ExponentialHistogram {
+            data_points: series
+                .iter()
+                .map(|s| ExponentialHistogramDataPoint {
+                    attributes: key_values(&s.attributes.to_attributes()),
+                    start_time_unix_nano: data.start_time_unix_nano,
+                    time_unix_nano: data.time_unix_nano,
+                    count: s.value.count,
+                    sum: Some(s.value.sum),
+                    scale: s.value.scale,
+                    zero_count: s.value.zero_count,
+                    positive: Some(buckets(&s.value.positive)),
+                    negative: Some(buckets(&s.value.negative)),
+                    min: Some(s.value.min),
+                    max: Some(s.value.max),
+                    ..Default::default()
+                })
+                .collect(),
+            aggregation_temporality: temporality,
+        })
+    });
+
+    ExportMetricsServiceRequest {
+        resource_metrics: vec![ResourceMetrics {
+            resource: Some(resource(service_name)),
+            scope_metrics: vec![ScopeMetrics {
+                scope: Some(scope()),
+                metrics: counters
+                    .chain(up_down_counters)
+                    .chain(gauges)
+                    .chain(histograms)
+                    .chain(exponential_histograms)
+                    .collect(),
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+/// One `Metric` per run of same-named series
+fn metrics<'a, T>(
+    series: &'a [Series<T>],
+    data: impl Fn(&[Series<T>]) -> metric::Data + 'a,
+) -> impl Iterator<Item = Metric> + 'a {
+    series.chunk_by(|a, b| a.name == b.name).map(move |group| Metric {
+        name: group[0].name.clone(),
+        data: Some(data(group)),
+        ..Default::default()
+    })
+}
+
+/// Build an `ExportLogsServiceRequest` with the same resource and scope as
+/// [`trace_request`]
+pub fn logs_request(service_name: &str, logs: &[LogRecord]) -> ExportLogsServiceRequest {
+    ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: Some(resource(service_name)),
+            scope_logs: vec![ScopeLogs {
+                scope: Some(scope()),
+                log_records: logs.iter().map(to_proto_log).collect(),
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+fn to_proto_log(record: &LogRecord) -> opentelemetry_proto::tonic::logs::v1::LogRecord {
+    let time = unix_nanos(record.timestamp);
+    opentelemetry_proto::tonic::logs::v1::LogRecord {
+        time_unix_nano: time,
+        observed_time_unix_nano: time,
+        severity_number: record.severity.number(),
+        severity_text: record.severity.as_str().to_string(),
+        body: Some(any_value(&AttributeValue::String(record.body.clone()))),
+        attributes: key_values(&record.attributes),
+        dropped_attributes_count: 0,
+        flags: record.trace_id.map_or(0, |_| u32::from(record.trace_flags.to_u8())),
+        trace_id: record.trace_id.map(|id| id.to_bytes().to_vec()).unwrap_or_default(),
+        span_id: record.span_id.map(|id| id.to_bytes().to_vec()).unwrap_or_default(),
+    }
+}
+
+fn resource(service_name: &str) -> Resource {
+    Resource {
+        attributes: vec![key_value("service.name", &AttributeValue::from(service_name))],
+        dropped_attributes_count: 0,
+    }
+}
+
+fn scope() -> InstrumentationScope {
+    InstrumentationScope {
+        name: "sigma-telemetry".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Default::default()
+    }
+}
+
+fn to_proto_span(record: &SpanRecord) -> Span {
+    let start = unix_nanos(record.start_time);
+    let end = start + record.duration.map_or(0, |d| d.as_nanos() as u64);
+    Span {
+        trace_id: record.trace_id.to_bytes().to_vec(),
+        span_id: record.span_id.to_bytes().to_vec(),
+        trace_state: record.trace_state.header(),
+        parent_span_id: record.parent_span_id.map(|id| id.to_bytes().to_vec()).unwrap_or_default(),
+        name: record.name.clone(),
+        kind: proto_span_kind(record.kind) as i32,
+        start_time_unix_nano: start,
+        end_time_unix_nano: end,
+        attributes: key_values(&record.attributes),
+        dropped_attributes_count: 0,
+        events: record
+            .events
+            .iter()
+            .map(|e| span::Event {
+                time_unix_nano: unix_nanos(e.timestamp),
+                name: e.name.clone(),
+                attributes: key_values(&e.attributes),
+                dropped_attributes_count: 0,
+            })
+            .collect(),
+        dropped_events_count: 0,
+        links: record
+            .links
+            .iter()
+            .map(|l| span::Link {
+                trace_id: l.trace_id.to_bytes().to_vec(),
+                span_id: l.span_id.to_bytes().to_vec(),
+                trace_state: l.trace_state.header(),
+                attributes: key_values(&l.attributes),
+                dropped_attributes_count: 0,
+            })
+            .collect(),
+        dropped_links_count: 0,
+        status: Some(match &record.status {
+            SpanStatus::Unset => Status {
+                message: String::new(),
+                code: status::StatusCode::Unset as i32,
+            },
+            SpanStatus::Ok => Status {
+                message: String::new(),
+                code: status::StatusCode::Ok as i32,
+            },
+            SpanStatus::Error(msg) => Status {
+                message: msg.clone(),
+                code: status::StatusCode::Error as i32,
+            },
+        }),
+    }
+}
+
+fn proto_span_kind(kind: SpanKind) -> span::SpanKind {
+    match kind {
+        SpanKind::Internal => span::SpanKind::Internal,
+        SpanKind::Server => span::SpanKind::Server,
+        SpanKind::Client => span::SpanKind::Client,
+        SpanKind::Producer => span::SpanKind::Producer,
+        SpanKind::Consumer => span::SpanKind::Consumer,
+    }
+}
+
+fn key_values(attributes: &Attributes) -> Vec<KeyValue> {
+    attributes.iter().map(|(k, v)| key_value(k, v)).collect()
+}
+
+fn key_value(key: &str, value: &AttributeValue) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(any_value(value)),
+    }
+}
+
+fn any_value(value: &AttributeValue) -> AnyValue {
+    fn array(values: Vec<any_value::Value>) -> any_value::Value {
+        any_value::Value::ArrayValue(ArrayValue {
+            values: values.into_iter().map(|v| AnyValue { value: Some(v) }).collect(),
+        })
+    }
+    let value = match value {
+        AttributeValue::String(v) => any_value::Value::StringValue(v.clone()),
+        AttributeValue::Int(v) => any_value::Value::IntValue(*v),
+        AttributeValue::Double(v) => any_value::Value::DoubleValue(*v),
+        AttributeValue::Bool(v) => any_value::Value::BoolValue(*v),
+        AttributeValue::StringArray(v) => array(v.iter().cloned().map(any_value::Value::StringValue).collect()),
+        AttributeValue::IntArray(v) => array(v.iter().copied().map(any_value::Value::IntValue).collect()),
+        AttributeValue::DoubleArray(v) => array(v.iter().copied().map(any_value::Value::DoubleValue).collect()),
+        AttributeValue::BoolArray(v) => array(v.iter().copied().map(any_value::Value::BoolValue).collect()),
+    };
+    AnyValue { value: Some(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+    use crate::{SigmaTelemetry, SpanOperation};
+
+    #[test]
+    fn test_trace_request_structure() {
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        let mut root = t.start_span("req", SpanOperation::Inference);
+        root.set_attribute("tokens", 12);
+        root.child_span_with_kind("decode", SpanOperation::TokenGeneration, SpanKind::Client)
+            .set_error("oom");
+        root.set_ok();
+        let spans = t.drain_spans(usize::MAX);
+
+        let request = trace_request("ryzanstein", &spans);
+        let scope = &request.resource_spans[0].scope_spans[0];
+        let (child, root) = (&scope.spans[0], &scope.spans[1]);
+        assert_eq!(root.trace_id, spans[1].trace_id.to_bytes().to_vec());
+        assert_eq!(child.parent_span_id, root.span_id);
+        assert!(root.parent_span_id.is_empty());
+        assert_eq!(root.kind, span::SpanKind::Internal as i32);
+        assert_eq!(child.kind, span::SpanKind::Client as i32);
+        assert!(root.end_time_unix_nano >= root.start_time_unix_nano);
+        assert_eq!(child.status.as_ref().unwrap().code, status::StatusCode::Error as i32);
+        assert_eq!(
+            root.attributes[0].value.as_ref().unwrap().value,
+            Some(any_value::Value::IntValue(12))
+        );
+    }
+
+    #[test]
+    fn test_trace_state_exported() {
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        let mut remote = crate::SpanContext::new(crate::TraceId::random(), crate::SpanId::random());
+        remote.trace_state = crate::TraceState::from_header("gateway=edge1").unwrap();
+        let options = crate::SpanOptions {
+            parent: Some(remote.clone()),
+            links: vec![crate::SpanLink::new(&remote)],
+            ..Default::default()
+        };
+        t.start_span_with_options("serve", SpanOperation::Inference, options).set_ok();
+
+        let request = trace_request("ryzanstein", &t.drain_spans(usize::MAX));
+        let span = &request.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.trace_state, "gateway=edge1");
+        assert_eq!(span.links[0].trace_state, "gateway=edge1");
+    }
+
+    #[test]
+    fn test_logs_request_correlation() {
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        let span = t.start_span("req", SpanOperation::Inference);
+        span.log(crate::Severity::Error, "decode failed", &[]);
+        t.log(crate::Severity::Debug, "idle", &[]);
+        let logs = t.drain_logs(usize::MAX);
+
+        let request = logs_request("ryzanstein", &logs);
+        let records = &request.resource_logs[0].scope_logs[0].log_records;
+        assert_eq!(records[0].trace_id, span.span_context().trace_id.to_bytes().to_vec());
+        assert_eq!(records[0].severity_number, 17);
+        assert_eq!(records[0].flags, 1);
+        assert!(records[1].trace_id.is_empty() && records[1].span_id.is_empty());
+    }
+
+    #[test]
+    fn test_metrics_request_structure() {
+        let mut config = TelemetryConfig::default();
+        config
+            .histograms
+            .overrides
+            .insert("load_ms".to_string(), crate::histogram::HistogramAggregation::exponential());
+        let t = SigmaTelemetry::new(config);
+        t.metrics().increment_by("requests", 3);
+        t.metrics().increment_with("requests", &[("model", "bitnet-3b")]);
+        t.metrics().add_up_down("active", -2);
+        t.metrics().set_gauge("gpu", 85.0);
+        t.metrics().record_histogram("latency_ms", 7.0);
+        t.metrics().record_histogram("load_ms", 4.0);
+        let data = t.metrics().export_data();
+
+        let request = metrics_request("ryzanstein", &data);
+        let resource_metrics = &request.resource_metrics[0];
+        assert_eq!(resource_metrics.resource, Some(resource("ryzanstein")));
+        let metrics = &resource_metrics.scope_metrics[0].metrics;
+        let Some(metric::Data::Sum(sum)) = &metrics[0].data else { panic!("counter must be a Sum") };
+        assert!(sum.is_monotonic);
+        assert_eq!(sum.data_points[0].value, Some(number_data_point::Value::AsInt(3)));
+        assert!(sum.data_points[0].attributes.is_empty());
+        assert_eq!(sum.data_points[1].attributes, vec![key_value("model", &AttributeValue::from("bitnet-3b"))]);
+        let Some(metric::Data::Sum(up_down)) = &metrics[1].data else { panic!("up-down counter must be a Sum") };
+        assert!(!up_down.is_monotonic);
+        assert_eq!(up_down.data_points[0].value, Some(number_data_point::Value::AsInt(-2)));
+        assert!(matches!(metrics[2].data, Some(metric::Data::Gauge(_))));
+        let Some(metric::Data::Histogram(histogram)) = &metrics[3].data else { panic!("expected Histogram") };
+        assert_eq!(histogram.data_points[0].bucket_counts[2], 1);
+        assert_eq!(histogram.aggregation_temporality, AggregationTemporality::Cumulative as i32);
+        let Some(metric::Data::ExponentialHistogram(exponential)) = &metrics[4].data else {
+            panic!("expected ExponentialHistogram")
+        };
+        let point = &exponential.data_points[0];
+        assert_eq!(point.scale, 20);
+        // 4 = 2^2 bounds bucket (2 << 20) - 1
+        assert_eq!(point.positive.as_ref().unwrap().offset, (2 << 20) - 1);
+    }
+}
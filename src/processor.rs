@@ -0,0 +1,359 @@
+//! Background batch export of finished spans, log records and metrics.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::error::TelemetryError;
+use crate::exporter::Exporter;
+use crate::SigmaTelemetry;
+
+enum Command {
+    Flush(oneshot::Sender<Result<usize, TelemetryError>>),
+    Shutdown(oneshot::Sender<Result<usize, TelemetryError>>),
+}
+
+/// Tokio task that drains finished spans from [`SigmaTelemetry`] into an
+/// [`Exporter`].
+///
+/// A batch is exported every `export_interval_secs` (0 disables the timer) and
+/// whenever `max_buffer_size` spans are waiting. Batches are at most
+/// `max_buffer_size` spans and are exported one at a time, so a slow backend
+/// leaves spans in the bounded span queue, where the configured
+/// [`crate::config::OverflowPolicy`] applies. A batch the
+/// exporter rejects is dropped and counted under `spans.export_failed`.
+///
+/// Log records are batched and exported the same way, alongside spans, and
+/// counted under `logs.exported` and `logs.export_failed`. With
+/// `traces_enabled` off, finished spans are drained and discarded.
+///
+/// When `metrics_enabled` is set, each tick and shutdown also export a
+/// snapshot of the [`crate::MetricsCollector`], collected with
+/// [`Exporter::collect_metrics`]; failures are counted under
+/// `metrics.export_failed`.
+pub struct BatchSpanProcessor {
+    commands: mpsc::Sender<Command>,
+    task: JoinHandle<()>,
+}
+
+impl BatchSpanProcessor {
+    /// Spawn the processor on the current tokio runtime
+    pub fn start(telemetry: Arc<SigmaTelemetry>, exporter: Exporter) -> Self {
+        let (commands, rx) = mpsc::channel(8);
+        let task = tokio::spawn(run(telemetry, Arc::new(exporter), rx));
+        Self { commands, task }
+    }
+
+    /// Export every buffered span and log record now, returning how many
+    /// spans were exported
+    pub async fn force_flush(&self) -> Result<usize, TelemetryError> {
+        let (tx, rx) = oneshot::channel();
+        self.commands
+            .send(Command::Flush(tx))
+            .await
+            .map_err(|_| TelemetryError::ExportError("batch processor has stopped".into()))?;
+        rx.await
+            .map_err(|_| TelemetryError::ExportError("batch processor has stopped".into()))?
+    }
+
+    /// Flush remaining spans and logs and stop the background task
+    pub async fn shutdown(self) -> Result<usize, TelemetryError> {
+        let (tx, rx) = oneshot::channel();
+        self.commands
+            .send(Command::Shutdown(tx))
+            .await
+            .map_err(|_| TelemetryError::ExportError("batch processor has stopped".into()))?;
+        let result = rx
+            .await
+            .map_err(|_| TelemetryError::ExportError("batch processor has stopped".into()))?;
+        let _ = self.task.await;
+        result
+    }
+}
+
+async fn run(telemetry: Arc<SigmaTelemetry>, exporter: Arc<Exporter>, mut commands: mpsc::Receiver<Command>) {
+    let interval_secs = telemetry.config.export_interval_secs;
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if interval_secs > 0 => {
+                telemetry.flush_pending_traces(false);
+                let _ = export_pending(&telemetry, &exporter).await;
+                export_pending_logs(&telemetry, &exporter).await;
+                export_metrics(&telemetry, &exporter).await;
+            }
+            _ = telemetry.active_spans.ready.notified() => {
+                let _ = export_pending(&telemetry, &exporter).await;
+            }
+            _ = telemetry.logs.ready.notified() => {
+                export_pending_logs(&telemetry, &exporter).await;
+            }
+            command = commands.recv() => match command {
+                Some(Command::Flush(reply)) => {
+                    telemetry.flush_pending_traces(false);
+                    let result = export_pending(&telemetry, &exporter).await;
+                    export_pending_logs(&telemetry, &exporter).await;
+                    let _ = reply.send(result);
+                }
+                Some(Command::Shutdown(reply)) => {
+                    telemetry.flush_pending_traces(true);
+                    let result = export_pending(&telemetry, &exporter).await;
+                    export_pending_logs(&telemetry, &exporter).await;
+                    export_metrics(&telemetry, &exporter).await;
+                    let _ = reply.send(result);
+                    break;
+                }
+                None => {
+                    telemetry.flush_pending_traces(true);
+                    let _ = export_pending(&telemetry, &exporter).await;
+                    export_pending_logs(&telemetry, &exporter).await;
+                    export_metrics(&telemetry, &exporter).await;
+                    break;
+                }
+            },
+        }
+    }
+}
+
+/// Export buffered spans batch by batch until the buffer is empty. With
+/// `traces_enabled` off the spans are discarded instead.
+async fn export_pending(telemetry: &Arc<SigmaTelemetry>, exporter: &Arc<Exporter>) -> Result<usize, TelemetryError> {
+    let batch_size = telemetry.config.max_buffer_size.max(1);
+    if !telemetry.config.traces_enabled {
+        while !telemetry.drain_spans(batch_size).is_empty() {}
+        return Ok(0);
+    }
+    let mut exported = 0;
+    let mut last_error = None;
+    loop {
+        let batch = telemetry.drain_spans(batch_size);
+        if batch.is_empty() {
+            break;
+        }
+        let count = batch.len();
+        // The OTLP path uses a blocking HTTP client, which must stay off the runtime threads
+        let exporter = Arc::clone(exporter);
+        let result = tokio::task::spawn_blocking(move || exporter.export(&batch))
+            .await
+            .map_err(|e| TelemetryError::ExportError(e.to_string()))
+            .and_then(|r| r);
+        match result {
+            Ok(_) => {
+                exported += count;
+                telemetry.metrics.increment_by("spans.exported", count as u64);
+            }
+            Err(e) => {
+                telemetry.metrics.increment_by("spans.export_failed", count as u64);
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(exported),
+    }
+}
+
+/// Export buffered log records batch by batch until the buffer is empty
+async fn export_pending_logs(telemetry: &Arc<SigmaTelemetry>, exporter: &Arc<Exporter>) {
+    let batch_size = telemetry.config.max_buffer_size.max(1);
+    loop {
+        let batch = telemetry.drain_logs(batch_size);
+        if batch.is_empty() {
+            break;
+        }
+        let count = batch.len() as u64;
+        let exporter = Arc::clone(exporter);
+        let result = tokio::task::spawn_blocking(move || exporter.export_logs(&batch))
+            .await
+            .map_err(|e| TelemetryError::ExportError(e.to_string()))
+            .and_then(|r| r);
+        match result {
+            Ok(_) => telemetry.metrics.increment_by("logs.exported", count),
+            Err(_) => telemetry.metrics.increment_by("logs.export_failed", count),
+        }
+    }
+}
+
+/// Export a metrics snapshot if metrics are enabled and any exist
+async fn export_metrics(telemetry: &Arc<SigmaTelemetry>, exporter: &Arc<Exporter>) {
+    if !telemetry.config.metrics_enabled {
+        return;
+    }
+    let data = exporter.collect_metrics(&telemetry.metrics);
+    if data.is_empty() {
+        return;
+    }
+    let exporter = Arc::clone(exporter);
+    let result = tokio::task::spawn_blocking(move || exporter.export_metrics(&data))
+        .await
+        .map_err(|e| TelemetryError::ExportError(e.to_string()))
+        .and_then(|r| r);
+    if let Err(e) = result {
+        tracing::warn!(error = %e, "metrics export failed");
+        telemetry.metrics.increment("metrics.export_failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+    use crate::exporter::ExportFormat;
+    use crate::SpanOperation;
+
+    fn telemetry(config: TelemetryConfig) -> Arc<SigmaTelemetry> {
+        Arc::new(SigmaTelemetry::new(config))
+    }
+
+    #[tokio::test]
+    async fn test_force_flush_drains_buffer() {
+        let t = telemetry(TelemetryConfig::default());
+        let processor = BatchSpanProcessor::start(
+            Arc::clone(&t),
+            Exporter::new(TelemetryConfig::default(), ExportFormat::Json),
+        );
+        for _ in 0..3 {
+            t.start_span("step", SpanOperation::TokenGeneration).set_ok();
+        }
+
+        assert_eq!(processor.force_flush().await.unwrap(), 3);
+        assert_eq!(t.snapshot().span_count, 0);
+        assert_eq!(t.metrics().get_counter("spans.exported"), 3);
+        assert_eq!(processor.force_flush().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_triggers_export() {
+        let config = TelemetryConfig {
+            export_interval_secs: 0,
+            max_buffer_size: 4,
+            ..TelemetryConfig::default()
+        };
+        let t = telemetry(config.clone());
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Json));
+        for _ in 0..4 {
+            t.start_span("step", SpanOperation::TokenGeneration).set_ok();
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while t.metrics().get_counter("spans.exported") < 4 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("buffer-full export did not run");
+        processor.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_interval_triggers_export() {
+        let t = telemetry(TelemetryConfig::default());
+        let _processor = BatchSpanProcessor::start(
+            Arc::clone(&t),
+            Exporter::new(TelemetryConfig::default(), ExportFormat::Json),
+        );
+        t.start_span("load", SpanOperation::ModelLoad).set_ok();
+
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        while t.metrics().get_counter("spans.exported") < 1 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(t.snapshot().span_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_exports_metrics() {
+        let collector = crate::test_support::HttpCollector::start();
+        let config = TelemetryConfig {
+            otlp_http_endpoint: collector.endpoint.clone(),
+            ..TelemetryConfig::default()
+        };
+        let t = telemetry(config.clone());
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Otlp));
+        t.start_span("req", SpanOperation::Inference).set_ok();
+        processor.shutdown().await.unwrap();
+
+        let metrics = &collector.requests_to("/v1/metrics")[0].json()["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        let names: Vec<_> = metrics.as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap()).collect();
+        assert!(names.contains(&"spans.total"), "{names:?}");
+        assert!(names.contains(&"span.inference.duration_ms"), "{names:?}");
+    }
+
+    #[tokio::test]
+    async fn test_flush_exports_logs() {
+        let collector = crate::test_support::HttpCollector::start();
+        let config = TelemetryConfig {
+            otlp_http_endpoint: collector.endpoint.clone(),
+            ..TelemetryConfig::default()
+        };
+        let t = telemetry(config.clone());
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Otlp));
+        let span = t.start_span("req", SpanOperation::Inference);
+        span.log(crate::Severity::Warn, "draft rejected", &[]);
+        span.set_ok();
+
+        assert_eq!(processor.force_flush().await.unwrap(), 1);
+        assert_eq!(t.metrics().get_counter("logs.exported"), 1);
+        let body = collector.requests_to("/v1/logs")[0].json();
+        let spans = collector.requests_to("/v1/traces")[0].json();
+        assert_eq!(
+            body["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0]["spanId"],
+            spans["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["spanId"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_metrics_export_disabled() {
+        let collector = crate::test_support::HttpCollector::start();
+        let config = TelemetryConfig {
+            otlp_http_endpoint: collector.endpoint.clone(),
+            metrics_enabled: false,
+            ..TelemetryConfig::default()
+        };
+        let t = telemetry(config.clone());
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Otlp));
+        t.start_span("req", SpanOperation::Inference).set_ok();
+        processor.shutdown().await.unwrap();
+        assert!(collector.requests_to("/v1/metrics").is_empty());
+        assert_eq!(collector.requests_to("/v1/traces").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_traces_disabled_skips_span_export() {
+        let collector = crate::test_support::HttpCollector::start();
+        let config = TelemetryConfig {
+            otlp_http_endpoint: collector.endpoint.clone(),
+            traces_enabled: false,
+            ..TelemetryConfig::default()
+        };
+        let t = telemetry(config.clone());
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Otlp));
+        t.start_span("req", SpanOperation::Inference).set_ok();
+
+        assert_eq!(processor.force_flush().await.unwrap(), 0);
+        assert_eq!(t.snapshot().span_count, 0, "spans are still drained");
+        processor.shutdown().await.unwrap();
+        assert!(collector.requests_to("/v1/traces").is_empty());
+        assert_eq!(collector.requests_to("/v1/metrics").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_export_counted() {
+        let config = TelemetryConfig {
+            otlp_http_endpoint: "http://127.0.0.1:1".to_string(),
+            ..TelemetryConfig::default()
+        };
+        let t = telemetry(config.clone());
+        let processor = BatchSpanProcessor::start(Arc::clone(&t), Exporter::new(config, ExportFormat::Otlp));
+        t.start_span("req", SpanOperation::Inference).set_ok();
+
+        assert!(processor.shutdown().await.is_err());
+        assert_eq!(t.metrics().get_counter("spans.export_failed"), 1);
+    }
+}
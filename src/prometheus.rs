@@ -0,0 +1,482 @@
+//! Prometheus text and OpenMetrics exposition of collected metrics.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Take};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::task::JoinHandle;
+
+use crate::error::TelemetryError;
+use crate::{HistogramData, MetricAttributes, MetricsData, Series, SigmaTelemetry, DEFAULT_HISTOGRAM_BOUNDS};
+
+/// `Content-Type` of [`render_text`] output
+pub const TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// `Content-Type` of [`render_openmetrics`] output
+pub const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    OpenMetrics,
+}
+
+/// Render `data` in the Prometheus text exposition format (0.0.4)
+pub fn render_text(data: &MetricsData) -> String {
+    render(data, Format::Text)
+}
+
+/// Render `data` in the OpenMetrics 1.0 text format, terminated by `# EOF`
+pub fn render_openmetrics(data: &MetricsData) -> String {
+    render(data, Format::OpenMetrics)
+}
+
+/// Map a dotted metric name such as `ryzanstein.inference.latency_ms` to a
+/// valid Prometheus name (`[a-zA-Z_:][a-zA-Z0-9_:]*`) by replacing every
+/// other character with `_`
+pub fn sanitize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 1);
+    for (i, c) in name.chars().enumerate() {
+        if i == 0 && c.is_ascii_digit() {
+            out.push('_');
+        }
+        out.push(if c.is_ascii_alphanumeric() || c == '_' || c == ':' { c } else { '_' });
+    }
+    if out.is_empty() {
+        out.push('_');
+    }
+    out
+}
+
+fn render(data: &MetricsData, format: Format) -> String {
+    let mut out = String::new();
+    let mut families = Families::default();
+    for (base, series) in families.group(&data.counters, "counter", |name| {
+        // Counter samples carry the `_total` suffix, so a name already
+        // ending in `.total` or `_total` is not given another
+        let name = sanitize_name(name);
+        match name.strip_suffix("_total") {
+            Some(base) if !base.is_empty() => base.to_string(),
+            _ => name,
+        }
+    }) {
+        // OpenMetrics names the family without the suffix
+        let family = match format {
+            Format::Text => format!("{base}_total"),
+            Format::OpenMetrics => base.clone(),
+        };
+        families.reserve(format!("{base}_total"), "counter");
+        header(&mut out, &family, &series[0].name, "counter");
+        for s in series {
+            let _ = writeln!(out, "{base}_total{} {}", labels(&s.attributes, None), s.value);
+        }
+    }
+    for (family, series) in families.group(&data.gauges, "gauge", sanitize_name) {
+        header(&mut out, &family, &series[0].name, "gauge");
+        for s in series {
+            let _ = writeln!(out, "{family}{} {}", labels(&s.attributes, None), number(s.value));
+        }
+    }
+    // Prometheus has no up-down counter type; they are gauges
+    for (family, series) in families.group(&data.up_down_counters, "gauge", sanitize_name) {
+        header(&mut out, &family, &series[0].name, "gauge");
+        for s in series {
+            let _ = writeln!(out, "{family}{} {}", labels(&s.attributes, None), s.value);
+        }
+    }
+    for (family, series) in families.group(&data.histograms, "histogram", sanitize_name) {
+        header(&mut out, &family, &series[0].name, "histogram");
+        for s in series {
+            write_histogram(&mut out, &family, &s.attributes, &s.value);
+        }
+    }
+    // The text formats have no exponential histograms
+    for (family, series) in families.group(&data.exponential_histograms, "histogram", sanitize_name) {
+        header(&mut out, &family, &series[0].name, "histogram");
+        for s in series {
+            write_histogram(&mut out, &family, &s.attributes, &s.value.to_explicit(&DEFAULT_HISTOGRAM_BOUNDS));
+        }
+    }
+    if format == Format::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+/// Families already written, by sanitized name
+#[derive(Default)]
+struct Families {
+    kinds: HashMap<String, &'static str>,
+}
+
+impl Families {
+    /// Take a sample name such as a counter's `_total` so no later family
+    /// uses it
+    fn reserve(&mut self, name: String, kind: &'static str) {
+        self.kinds.insert(name, kind);
+    }
+
+    /// Group `series` by family name, in order of first appearance. Names
+    /// that only differ before sanitizing, such as `a.b` and `a_b`, share a
+    /// family and the first series with each label set wins. Series whose
+    /// family name an earlier group already took are skipped.
+    fn group<'a, T>(
+        &mut self,
+        series: &'a [Series<T>],
+        kind: &'static str,
+        family: impl Fn(&str) -> String,
+    ) -> Vec<(String, Vec<&'a Series<T>>)> {
+        let mut groups: Vec<(String, Vec<&Series<T>>)> = Vec::new();
+        let mut index = HashMap::new();
+        for s in series {
+            let name = family(&s.name);
+            let i = match index.get(&name) {
+                Some(&i) => i,
+                None => {
+                    if let Some(&existing) = self.kinds.get(&name) {
+                        tracing::warn!(metric = %s.name, family = %name, existing, "prometheus family name already used");
+                        continue;
+                    }
+                    self.kinds.insert(name.clone(), kind);
+                    index.insert(name.clone(), groups.len());
+                    groups.push((name, Vec::new()));
+                    groups.len() - 1
+                }
+            };
+            let members = &mut groups[i].1;
+            if members.iter().any(|m| m.attributes == s.attributes) {
+                tracing::warn!(metric = %s.name, family = %groups[i].0, "duplicate prometheus series dropped");
+                continue;
+            }
+            members.push(s);
+        }
+        groups
+    }
+}
+
+fn header(out: &mut String, family: &str, original: &str, kind: &str) {
+    let help = original.replace('\\', "\\\\").replace('\n', "\\n");
+    let _ = writeln!(out, "# HELP {family} {help}");
+    let _ = writeln!(out, "# TYPE {family} {kind}");
+}
+
+fn write_histogram(out: &mut String, family: &str, attributes: &MetricAttributes, histogram: &HistogramData) {
+    let mut cumulative = 0;
+    for (i, count) in histogram.bucket_counts.iter().enumerate() {
+        cumulative += count;
+        let le = histogram.bounds.get(i).map_or_else(|| "+Inf".to_string(), |b| number(*b));
+        let _ = writeln!(out, "{family}_bucket{} {cumulative}", labels(attributes, Some(&le)));
+    }
+    let labels = labels(attributes, None);
+    let _ = writeln!(out, "{family}_sum{labels} {}", number(histogram.sum));
+    let _ = writeln!(out, "{family}_count{labels} {}", histogram.count);
+}
+
+/// `{key="value",...}` for a series, with `le` appended for histogram
+/// buckets; empty when there are no labels. Label names are sanitized like
+/// metric names, minus `:`, and values escaped.
+fn labels(attributes: &MetricAttributes, le: Option<&str>) -> String {
+    let pairs: Vec<String> = attributes
+        .iter()
+        .map(|(k, v)| (sanitize_name(k).replace(':', "_"), v))
+        .chain(le.map(|le| ("le".to_string(), le)))
+        .map(|(k, v)| {
+            let v = v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{k}=\"{v}\"")
+        })
+        .collect();
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Sample value as Prometheus spells it, including `+Inf`, `-Inf` and `NaN`
+fn number(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        format!("{v:?}")
+    }
+}
+
+/// Minimal HTTP listener serving `GET /metrics` for Prometheus scrapes.
+///
+/// Scrapers sending `Accept: application/openmetrics-text` get OpenMetrics,
+/// everything else gets the text format. Any other path is a 404.
+pub struct MetricsListener {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl MetricsListener {
+    /// Bind `addr` and serve on the current tokio runtime
+    pub async fn bind(telemetry: Arc<SigmaTelemetry>, addr: impl ToSocketAddrs) -> Result<Self, TelemetryError> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let telemetry = Arc::clone(&telemetry);
+                tokio::spawn(async move {
+                    if let Err(e) = respond(stream, &telemetry).await {
+                        tracing::debug!(error = %e, "metrics scrape failed");
+                    }
+                });
+            }
+        });
+        Ok(Self { local_addr, task })
+    }
+
+    /// Address actually bound, useful with port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting scrapes
+    pub fn shutdown(self) {
+        self.task.abort();
+    }
+}
+
+/// Most bytes read of a scrape request, request line and headers together
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+/// Most header lines read of a scrape request
+const MAX_HEADERS: usize = 64;
+
+/// How long a scraper gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn respond(stream: TcpStream, telemetry: &SigmaTelemetry) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_BYTES));
+    let (request_line, openmetrics) = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut reader))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "scrape request timed out"))??;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+    let (status, content_type, body) = if method == "GET" && path == "/metrics" {
+        let data = telemetry.metrics().export_data();
+        if openmetrics {
+            ("200 OK", OPENMETRICS_CONTENT_TYPE, render_openmetrics(&data))
+        } else {
+            ("200 OK", TEXT_CONTENT_TYPE, render_text(&data))
+        }
+    } else {
+        ("404 Not Found", "text/plain; charset=utf-8", "not found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let stream = reader.get_mut().get_mut();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// The request line, and whether the scraper accepts OpenMetrics
+async fn read_request(reader: &mut BufReader<Take<TcpStream>>) -> std::io::Result<(String, bool)> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut openmetrics = false;
+    for _ in 0..MAX_HEADERS {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" || line == "\n" {
+            return Ok((request_line, openmetrics));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("accept") && value.contains("application/openmetrics-text") {
+                openmetrics = true;
+            }
+        }
+    }
+    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "too many request headers"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+    use crate::Temporality;
+
+    fn sample() -> MetricsData {
+        MetricsData {
+            start_time_unix_nano: 0,
+            time_unix_nano: 0,
+            temporality: Temporality::Cumulative,
+            counters: vec![Series::new("ryzanstein.inference.requests", MetricAttributes::default(), 42)],
+            gauges: vec![Series::new("ryzanstein.system.gpu_utilization", MetricAttributes::default(), 85.5)],
+            up_down_counters: Vec::new(),
+            histograms: vec![Series::new(
+                "ryzanstein.inference.latency_ms",
+                MetricAttributes::default(),
+                HistogramData {
+                    count: 3,
+                    sum: 36.0,
+                    min: 4.0,
+                    max: 20.0,
+                    bounds: vec![5.0, 10.0],
+                    bucket_counts: vec![1, 1, 1],
+                },
+            )],
+            exponential_histograms: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("ryzanstein.kv_cache.hit_rate"), "ryzanstein_kv_cache_hit_rate");
+        assert_eq!(sanitize_name("span.kv-cache op"), "span_kv_cache_op");
+        assert_eq!(sanitize_name("9lives"), "_9lives");
+        assert_eq!(sanitize_name(""), "_");
+    }
+
+    #[test]
+    fn test_render_text() {
+        let text = render_text(&sample());
+        let expected = "\
+# HELP ryzanstein_inference_requests_total ryzanstein.inference.requests
+# TYPE ryzanstein_inference_requests_total counter
+ryzanstein_inference_requests_total 42
+# HELP ryzanstein_system_gpu_utilization ryzanstein.system.gpu_utilization
+# TYPE ryzanstein_system_gpu_utilization gauge
+ryzanstein_system_gpu_utilization 85.5
+# HELP ryzanstein_inference_latency_ms ryzanstein.inference.latency_ms
+# TYPE ryzanstein_inference_latency_ms histogram
+ryzanstein_inference_latency_ms_bucket{le=\"5.0\"} 1
+ryzanstein_inference_latency_ms_bucket{le=\"10.0\"} 2
+ryzanstein_inference_latency_ms_bucket{le=\"+Inf\"} 3
+ryzanstein_inference_latency_ms_sum 36.0
+ryzanstein_inference_latency_ms_count 3
+";
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_render_openmetrics() {
+        let text = render_openmetrics(&sample());
+        assert!(text.contains("# TYPE ryzanstein_inference_requests counter\nryzanstein_inference_requests_total 42\n"));
+        assert!(text.ends_with("# EOF\n"));
+        assert_eq!(number(f64::NEG_INFINITY), "-Inf");
+
+        let mut data = sample();
+        data.up_down_counters = vec![Series::new("ryzanstein.queue.depth", MetricAttributes::default(), -2)];
+        let text = render_openmetrics(&data);
+        assert!(text.contains("# TYPE ryzanstein_queue_depth gauge\nryzanstein_queue_depth -2\n"));
+    }
+
+    #[test]
+    fn test_render_labels() {
+        let mut data = sample();
+        let model = |m: &str| MetricAttributes::new(&[("model", m), ("tenant.id", "a\"b")]);
+        data.counters = vec![
+            Series::new("ryzanstein.inference.requests", model("bitnet-3b"), 2),
+            Series::new("ryzanstein.inference.requests", model("mamba-2.8b"), 5),
+        ];
+        data.histograms[0].attributes = model("bitnet-3b");
+        let text = render_text(&data);
+        assert_eq!(text.matches("# TYPE ryzanstein_inference_requests_total").count(), 1);
+        assert!(text.contains("ryzanstein_inference_requests_total{model=\"bitnet-3b\",tenant_id=\"a\\\"b\"} 2\n"));
+        assert!(text.contains("ryzanstein_inference_requests_total{model=\"mamba-2.8b\",tenant_id=\"a\\\"b\"} 5\n"));
+        assert!(text.contains("ryzanstein_inference_latency_ms_bucket{model=\"bitnet-3b\",tenant_id=\"a\\\"b\",le=\"+Inf\"} 3\n"));
+        assert!(text.contains("ryzanstein_inference_latency_ms_count{model=\"bitnet-3b\",tenant_id=\"a\\\"b\"} 3\n"));
+    }
+
+    #[test]
+    fn test_render_counter_already_total() {
+        let mut data = sample();
+        data.counters = vec![
+            Series::new("ryzanstein.tokens.total", MetricAttributes::default(), 7),
+            Series::new("ryzanstein_errors_total", MetricAttributes::default(), 2),
+        ];
+        let text = render_text(&data);
+        assert!(text.contains("# TYPE ryzanstein_tokens_total counter\nryzanstein_tokens_total 7\n"));
+        assert!(text.contains("# TYPE ryzanstein_errors_total counter\nryzanstein_errors_total 2\n"));
+        assert!(!text.contains("_total_total"));
+        let openmetrics = render_openmetrics(&data);
+        assert!(openmetrics.contains("# TYPE ryzanstein_errors counter\nryzanstein_errors_total 2\n"));
+    }
+
+    #[test]
+    fn test_render_merges_colliding_names() {
+        let mut data = sample();
+        let model = |m: &str| MetricAttributes::new(&[("model", m)]);
+        data.gauges = vec![
+            Series::new("kv.hit_rate", model("a"), 0.5),
+            Series::new("kv.hit_rate", model("b"), 0.6),
+            Series::new("kv.other", MetricAttributes::default(), 1.0),
+            Series::new("kv_hit_rate", model("a"), 0.9),
+            Series::new("kv_hit_rate", model("c"), 0.7),
+        ];
+        data.up_down_counters = vec![Series::new("kv-other", MetricAttributes::default(), 3)];
+        data.counters.push(Series::new("ryzanstein.inference.requests.total", MetricAttributes::default(), 1));
+        data.histograms[0].name = "ryzanstein.inference.requests_total".to_string();
+        let text = render_text(&data);
+        assert_eq!(text.matches("# TYPE kv_hit_rate gauge").count(), 1);
+        assert!(text.contains("kv_hit_rate{model=\"a\"} 0.5\nkv_hit_rate{model=\"b\"} 0.6\nkv_hit_rate{model=\"c\"} 0.7\n"));
+        assert!(!text.contains("0.9"), "first series with a label set wins");
+        assert_eq!(text.matches("# TYPE kv_other").count(), 1);
+        assert!(!text.contains("kv_other 3"));
+        assert_eq!(text.matches("# TYPE ryzanstein_inference_requests_total").count(), 1);
+        assert!(!text.contains("ryzanstein_inference_requests_total 1\n"));
+        assert!(!text.contains("histogram"), "counter samples keep their name");
+    }
+
+    async fn get(addr: SocketAddr, path: &str, accept: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nhost: localhost\r\naccept: {accept}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_listener_serves_scrapes() {
+        let telemetry = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+        telemetry.metrics().increment("ryzanstein.inference.requests");
+        let listener = MetricsListener::bind(Arc::clone(&telemetry), "127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr();
+
+        let text = get(addr, "/metrics", "text/plain").await;
+        assert!(text.starts_with("HTTP/1.1 200 OK"));
+        assert!(text.contains("content-type: text/plain; version=0.0.4"));
+        assert!(text.contains("ryzanstein_inference_requests_total 1\n"));
+
+        let openmetrics = get(addr, "/metrics", "application/openmetrics-text; version=1.0.0").await;
+        assert!(openmetrics.ends_with("# EOF\n"));
+        assert!(get(addr, "/other", "*/*").await.starts_with("HTTP/1.1 404"));
+        listener.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_listener_rejects_endless_headers() {
+        let telemetry = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+        let listener = MetricsListener::bind(telemetry, "127.0.0.1:0").await.unwrap();
+
+        let mut stream = TcpStream::connect(listener.local_addr()).await.unwrap();
+        let mut request = "GET /metrics HTTP/1.1\r\n".to_string();
+        (0..MAX_HEADERS).for_each(|i| request.push_str(&format!("x-{i}: y\r\n")));
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        assert_eq!(response, "", "closed without a response");
+
+        // Headers past the byte limit are not read, so the scrape is served
+        let mut stream = TcpStream::connect(listener.local_addr()).await.unwrap();
+        let long = format!("GET /metrics HTTP/1.1\r\nx-long: {}", "a".repeat(MAX_REQUEST_BYTES as usize));
+        stream.write_all(long.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        listener.shutdown();
+    }
+}
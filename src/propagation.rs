@@ -0,0 +1,196 @@
+//! W3C Trace Context propagation across process boundaries.
+
+use std::collections::HashMap;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::context::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+/// Header carrying version, trace ID, parent span ID and flags
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// Header carrying vendor-specific trace state
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+const SUPPORTED_VERSION: u8 = 0;
+
+/// Carrier that trace context can be written into
+pub trait Injector {
+    fn set(&mut self, key: &str, value: String);
+}
+
+/// Carrier that trace context can be read from
+pub trait Extractor {
+    fn get(&self, key: &str) -> Option<&str>;
+}
+
+impl Injector for HashMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_lowercase(), value);
+    }
+}
+
+impl Extractor for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        HashMap::get(self, key)
+            .or_else(|| self.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v))
+            .map(String::as_str)
+    }
+}
+
+impl Injector for HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(key.as_bytes()), HeaderValue::from_str(&value)) {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl Extractor for HeaderMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        HeaderMap::get(self, key).and_then(|v| v.to_str().ok())
+    }
+}
+
+/// Write `traceparent` and, when non-empty, `tracestate` for `context`.
+/// Invalid contexts are not injected.
+pub fn inject<I: Injector + ?Sized>(context: &SpanContext, carrier: &mut I) {
+    if !context.is_valid() {
+        return;
+    }
+    carrier.set(
+        TRACEPARENT_HEADER,
+        format!(
+            "{:02x}-{}-{}-{:02x}",
+            SUPPORTED_VERSION,
+            context.trace_id,
+            context.span_id,
+            context.trace_flags.to_u8()
+        ),
+    );
+    if !context.trace_state.is_empty() {
+        carrier.set(TRACESTATE_HEADER, context.trace_state.header());
+    }
+}
+
+/// Read a remote parent context from `traceparent`/`tracestate`.
+///
+/// Returns `None` when `traceparent` is missing or malformed. A malformed
+/// `tracestate` is discarded without invalidating the parent.
+pub fn extract<E: Extractor + ?Sized>(carrier: &E) -> Option<SpanContext> {
+    let (trace_id, span_id, trace_flags) = parse_traceparent(carrier.get(TRACEPARENT_HEADER)?)?;
+    let trace_state = carrier
+        .get(TRACESTATE_HEADER)
+        .and_then(|header| TraceState::from_header(header).ok())
+        .unwrap_or_default();
+    Some(SpanContext {
+        trace_id,
+        span_id,
+        trace_flags,
+        trace_state,
+        is_remote: true,
+    })
+}
+
+fn parse_traceparent(header: &str) -> Option<(TraceId, SpanId, TraceFlags)> {
+    let header = header.trim();
+    let parts: Vec<&str> = header.split('-').collect();
+    if parts.len() < 4 || parts[0].len() != 2 || !is_lower_hex(parts[0]) {
+        return None;
+    }
+    let version = u8::from_str_radix(parts[0], 16).ok()?;
+    // Version 00 has exactly four fields; later versions may append more
+    if version == 0xff || (version == SUPPORTED_VERSION && parts.len() != 4) {
+        return None;
+    }
+    let trace_id = TraceId::from_hex(parts[1]).ok().filter(TraceId::is_valid)?;
+    let span_id = SpanId::from_hex(parts[2]).ok().filter(SpanId::is_valid)?;
+    if parts[3].len() != 2 || !is_lower_hex(parts[3]) {
+        return None;
+    }
+    let flags = u8::from_str_radix(parts[3], 16).ok()?;
+    // Only the sampled bit is defined; unknown bits must not be propagated
+    Some((trace_id, span_id, TraceFlags::new(flags & TraceFlags::SAMPLED.to_u8())))
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes().all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn carrier(traceparent: &str) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        map.insert(TRACEPARENT_HEADER.to_string(), traceparent.to_string());
+        map
+    }
+
+    #[test]
+    fn test_extract_valid_traceparent() {
+        let cx = extract(&carrier(TRACEPARENT)).unwrap();
+        assert_eq!(cx.trace_id.to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(cx.span_id.to_string(), "00f067aa0ba902b7");
+        assert!(cx.trace_flags.is_sampled());
+        assert!(cx.is_remote);
+    }
+
+    #[test]
+    fn test_extract_rejects_malformed() {
+        for bad in [
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert!(extract(&carrier(bad)).is_none(), "accepted {bad}");
+        }
+        assert!(extract(&HashMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_extract_masks_unknown_flags() {
+        let cx = extract(&carrier("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-ff")).unwrap();
+        assert_eq!(cx.trace_flags.to_u8(), 0x01);
+    }
+
+    #[test]
+    fn test_extract_future_version() {
+        let cx = extract(&carrier("cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-09-what")).unwrap();
+        assert_eq!(cx.trace_flags, TraceFlags::SAMPLED);
+    }
+
+    #[test]
+    fn test_invalid_tracestate_dropped() {
+        let mut map = carrier(TRACEPARENT);
+        map.insert(TRACESTATE_HEADER.to_string(), "not a list".to_string());
+        let cx = extract(&map).unwrap();
+        assert!(cx.trace_state.is_empty());
+    }
+
+    #[test]
+    fn test_inject_extract_roundtrip_header_map() {
+        let mut cx = SpanContext::new(TraceId::random(), SpanId::random());
+        cx.trace_state.insert("ryzanstein", "worker-3").unwrap();
+
+        let mut headers = HeaderMap::new();
+        inject(&cx, &mut headers);
+        assert!(headers.contains_key("traceparent"));
+
+        let extracted = extract(&headers).unwrap();
+        assert_eq!(extracted.trace_id, cx.trace_id);
+        assert_eq!(extracted.span_id, cx.span_id);
+        assert_eq!(extracted.trace_state.get("ryzanstein"), Some("worker-3"));
+    }
+
+    #[test]
+    fn test_inject_skips_invalid_context() {
+        let mut map = HashMap::new();
+        inject(&SpanContext::new(TraceId::INVALID, SpanId::random()), &mut map);
+        assert!(map.is_empty());
+    }
+}
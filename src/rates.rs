@@ -0,0 +1,244 @@
+//! Rolling-window rates derived from counters.
+//!
+//! [`RateWindows`] samples counter totals once per interval and keeps the
+//! samples for a retention period, so rates such as tokens/sec over the last
+//! 1, 5 or 15 minutes are the difference between two samples. Rates can also
+//! be published as gauges, e.g. `ryzanstein.system.throughput_tps` from
+//! `ryzanstein.inference.tokens`.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+
+use crate::{MetricAttributes, MetricsCollector, SigmaTelemetry};
+
+/// The windows reported by [`RateWindows::rates`] and published as gauges,
+/// with their `window` attribute values
+pub const STANDARD_WINDOWS: [(Duration, &str); 3] = [
+    (Duration::from_secs(60), "1m"),
+    (Duration::from_secs(300), "5m"),
+    (Duration::from_secs(900), "15m"),
+];
+
+/// Per-second rates of one counter over the [`STANDARD_WINDOWS`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rates {
+    pub m1: f64,
+    pub m5: f64,
+    pub m15: f64,
+}
+
+/// Counter totals sampled every `interval` for the last `retention`
+#[derive(Debug)]
+pub struct RateWindows {
+    interval: Duration,
+    capacity: usize,
+    tracked: Mutex<Vec<Tracked>>,
+}
+
+#[derive(Debug)]
+struct Tracked {
+    name: String,
+    /// `None` sums every series of the counter
+    attributes: Option<MetricAttributes>,
+    /// Gauge receiving the standard window rates
+    gauge: Option<String>,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Default for RateWindows {
+    /// Ten-second intervals kept for fifteen minutes
+    fn default() -> Self {
+        Self::new(Duration::from_secs(10), Duration::from_secs(900))
+    }
+}
+
+impl RateWindows {
+    /// Windows up to `retention` long, with `interval` resolution. Windows
+    /// are rounded to whole intervals.
+    pub fn new(interval: Duration, retention: Duration) -> Self {
+        let interval = interval.max(Duration::from_millis(1));
+        Self {
+            interval,
+            capacity: retention.as_nanos().div_ceil(interval.as_nanos()) as usize + 1,
+            tracked: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Track the total of counter `name` across its series
+    pub fn track(&self, name: &str) {
+        self.add(name, None, None);
+    }
+
+    /// Track the series of counter `name` for `attributes`
+    pub fn track_with(&self, name: &str, attributes: &[(&str, &str)]) {
+        self.add(name, Some(MetricAttributes::new(attributes)), None);
+    }
+
+    /// Track the total of `counter` and set gauge `gauge` to its rates on
+    /// every sample, one series per window with a `window` attribute of
+    /// `1m`, `5m` or `15m`
+    pub fn publish(&self, counter: &str, gauge: &str) {
+        self.add(counter, None, Some(gauge.to_string()));
+    }
+
+    fn add(&self, name: &str, attributes: Option<MetricAttributes>, gauge: Option<String>) {
+        let mut tracked = self.tracked.lock().unwrap();
+        match tracked.iter_mut().find(|t| t.name == name && t.attributes == attributes) {
+            Some(existing) => existing.gauge = gauge.or(existing.gauge.take()),
+            None => tracked.push(Tracked {
+                name: name.to_string(),
+                attributes,
+                gauge,
+                samples: VecDeque::with_capacity(self.capacity),
+            }),
+        }
+    }
+
+    /// Record the current totals of every tracked counter and publish
+    /// gauges. [`Self::spawn`] calls this once per interval.
+    pub fn sample(&self, metrics: &MetricsCollector) {
+        self.sample_at(metrics, Instant::now());
+    }
+
+    fn sample_at(&self, metrics: &MetricsCollector, now: Instant) {
+        let mut tracked = self.tracked.lock().unwrap();
+        for t in tracked.iter_mut() {
+            let total = match &t.attributes {
+                None => metrics.get_counter_total(&t.name),
+                Some(attributes) => {
+                    let attributes: Vec<(&str, &str)> = attributes.iter().collect();
+                    metrics.get_counter_with(&t.name, &attributes)
+                }
+            };
+            if t.samples.len() == self.capacity {
+                t.samples.pop_front();
+            }
+            t.samples.push_back((now, total));
+            if let Some(gauge) = &t.gauge {
+                for (window, label) in STANDARD_WINDOWS {
+                    if let Some(rate) = window_rate(&t.samples, window) {
+                        metrics.set_gauge_with(gauge, rate, &[("window", label)]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Per-second rate of the tracked total of counter `name` over the last
+    /// `window`, or over every sample when fewer cover it. `None` until two
+    /// samples exist.
+    pub fn rate(&self, name: &str, window: Duration) -> Option<f64> {
+        self.rate_for(name, None, window)
+    }
+
+    /// [`Self::rate`] for a series added with [`Self::track_with`]
+    pub fn rate_with(&self, name: &str, attributes: &[(&str, &str)], window: Duration) -> Option<f64> {
+        self.rate_for(name, Some(MetricAttributes::new(attributes)), window)
+    }
+
+    /// Rates of the tracked total of counter `name` over the
+    /// [`STANDARD_WINDOWS`]
+    pub fn rates(&self, name: &str) -> Option<Rates> {
+        let [m1, m5, m15] = STANDARD_WINDOWS.map(|(window, _)| self.rate(name, window));
+        Some(Rates {
+            m1: m1?,
+            m5: m5?,
+            m15: m15?,
+        })
+    }
+
+    fn rate_for(&self, name: &str, attributes: Option<MetricAttributes>, window: Duration) -> Option<f64> {
+        let tracked = self.tracked.lock().unwrap();
+        let t = tracked.iter().find(|t| t.name == name && t.attributes == attributes)?;
+        window_rate(&t.samples, window)
+    }
+
+    /// Sample every interval on the current tokio runtime until the handle
+    /// is aborted
+    pub fn spawn(self: Arc<Self>, telemetry: Arc<SigmaTelemetry>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.sample(telemetry.metrics());
+            }
+        })
+    }
+}
+
+/// Rate between the newest sample and the newest one at least `window`
+/// older, else the oldest
+fn window_rate(samples: &VecDeque<(Instant, u64)>, window: Duration) -> Option<f64> {
+    let &(end, last) = samples.back()?;
+    let &(start, first) = samples
+        .iter()
+        .rev()
+        .find(|(at, _)| end.duration_since(*at) >= window)
+        .or(samples.front())?;
+    let elapsed = end.duration_since(start).as_secs_f64();
+    (elapsed > 0.0).then(|| last.saturating_sub(first) as f64 / elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::histogram::HistogramConfig;
+    use crate::metrics::MetricNames;
+
+    #[test]
+    fn test_rates_over_windows() {
+        let m = MetricsCollector::new(HistogramConfig::default());
+        let rates = RateWindows::default();
+        rates.track(MetricNames::INFERENCE_REQUESTS);
+        let start = Instant::now();
+        assert_eq!(rates.rate(MetricNames::INFERENCE_REQUESTS, Duration::from_secs(60)), None);
+
+        // 10 requests/sec for ten minutes, then 40/sec for a minute
+        for i in 0..=66u64 {
+            rates.sample_at(&m, start + Duration::from_secs(i * 10));
+            let per_sec = if i < 60 { 10 } else { 40 };
+            m.increment_by_with(MetricNames::INFERENCE_REQUESTS, per_sec * 10, &[("model", "bitnet-3b")]);
+        }
+        let r = rates.rates(MetricNames::INFERENCE_REQUESTS).unwrap();
+        assert_eq!(r.m1, 40.0);
+        assert_eq!(r.m5, (40.0 * 60.0 + 10.0 * 240.0) / 300.0);
+        // Only 660s of samples so far
+        assert_eq!(r.m15, (40.0 * 60.0 + 10.0 * 600.0) / 660.0);
+    }
+
+    #[test]
+    fn test_retention_bounds_samples() {
+        let m = MetricsCollector::new(HistogramConfig::default());
+        let rates = RateWindows::new(Duration::from_secs(10), Duration::from_secs(60));
+        rates.track_with("errors", &[("kind", "oom")]);
+        let start = Instant::now();
+        for i in 0..100u64 {
+            m.increment_with("errors", &[("kind", "oom")]);
+            rates.sample_at(&m, start + Duration::from_secs(i * 10));
+        }
+        assert_eq!(rates.tracked.lock().unwrap()[0].samples.len(), 7);
+        let rate = rates.rate_with("errors", &[("kind", "oom")], Duration::from_secs(900)).unwrap();
+        assert_eq!(rate, 0.1, "limited to the retained minute");
+        assert_eq!(rates.rate("errors", Duration::from_secs(60)), None, "untracked total");
+    }
+
+    #[test]
+    fn test_publish_gauges() {
+        let m = MetricsCollector::new(HistogramConfig::default());
+        let rates = RateWindows::default();
+        rates.publish(MetricNames::INFERENCE_TOKENS, MetricNames::THROUGHPUT_TPS);
+        let start = Instant::now();
+        rates.sample_at(&m, start);
+        assert_eq!(m.get_gauge_with(MetricNames::THROUGHPUT_TPS, &[("window", "1m")]), None);
+
+        m.increment_by(MetricNames::INFERENCE_TOKENS, 500);
+        rates.sample_at(&m, start + Duration::from_secs(10));
+        for window in ["1m", "5m", "15m"] {
+            assert_eq!(m.get_gauge_with(MetricNames::THROUGHPUT_TPS, &[("window", window)]), Some(50.0));
+        }
+    }
+}
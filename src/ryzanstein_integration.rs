@@ -1,12 +1,18 @@
 //! Ryzanstein-specific telemetry integration.
 
+use std::sync::Arc;
+
 use crate::config::TelemetryConfig;
 use crate::error::TelemetryError;
+use crate::spans::SpanTemplates;
+use crate::SigmaTelemetry;
 
 /// Client for Ryzanstein telemetry hooks
 pub struct RyzansteinTelemetryClient {
     base_url: String,
     client: Option<reqwest::Client>,
+    /// Traces outbound calls when set
+    telemetry: Option<Arc<SigmaTelemetry>>,
 }
 
 /// Health status
@@ -23,17 +29,22 @@ impl RyzansteinTelemetryClient {
         Self {
             base_url: config.ryzanstein_url.clone(),
             client: reqwest::Client::builder().build().ok(),
+            telemetry: None,
         }
     }
 
+    /// Record each call as a `Client` span in `telemetry` and send its
+    /// context in `traceparent`, so Ryzanstein can continue the trace
+    pub fn with_telemetry(mut self, telemetry: Arc<SigmaTelemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
     /// Probe Ryzanstein health
     pub async fn health_check(&self) -> Result<HealthStatus, TelemetryError> {
+        let client = self.client()?;
         let url = format!("{}/health", self.base_url);
-        let client = self.client.as_ref()
-            .ok_or_else(|| TelemetryError::RyzansteinError("HTTP client not initialized".into()))?;
-
-        let resp = client.get(&url).send().await
-            .map_err(|e| TelemetryError::RyzansteinError(e.to_string()))?;
+        let resp = self.send(client.get(&url), "GET", &url).await?;
 
         resp.json::<HealthStatus>().await
             .map_err(|e| TelemetryError::RyzansteinError(e.to_string()))
@@ -41,19 +52,52 @@ impl RyzansteinTelemetryClient {
 
     /// Push telemetry data to Ryzanstein
     pub async fn push_metrics(&self, snapshot: &crate::TelemetrySnapshot) -> Result<(), TelemetryError> {
+        let client = self.client()?;
         let url = format!("{}/v1/telemetry", self.base_url);
-        let client = self.client.as_ref()
-            .ok_or_else(|| TelemetryError::RyzansteinError("HTTP client not initialized".into()))?;
-
-        client.post(&url)
-            .json(snapshot)
-            .send()
-            .await
-            .map_err(|e| TelemetryError::RyzansteinError(e.to_string()))?;
+        self.send(client.post(&url).json(snapshot), "POST", &url).await?;
 
         Ok(())
     }
 
+    fn client(&self) -> Result<&reqwest::Client, TelemetryError> {
+        self.client.as_ref()
+            .ok_or_else(|| TelemetryError::RyzansteinError("HTTP client not initialized".into()))
+    }
+
+    /// Send `request`, inside a `Client` span when tracing. Error responses
+    /// are returned as before, but mark the span as failed.
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        method: &str,
+        url: &str,
+    ) -> Result<reqwest::Response, TelemetryError> {
+        let Some(telemetry) = &self.telemetry else {
+            return request.send().await.map_err(|e| TelemetryError::RyzansteinError(e.to_string()));
+        };
+        let (operation, options) = SpanTemplates::http_client(method, url);
+        let mut span = telemetry.start_owned_span_with_options(method, operation, options);
+        let mut headers = reqwest::header::HeaderMap::new();
+        span.inject_context(&mut headers);
+
+        match request.headers(headers).send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                span.set_attribute("http.response.status_code", status.as_u16() as i64);
+                if status.is_client_error() || status.is_server_error() {
+                    span.set_error(&status.to_string());
+                } else {
+                    span.set_ok();
+                }
+                Ok(resp)
+            }
+            Err(e) => {
+                span.set_error(&e.to_string());
+                Err(TelemetryError::RyzansteinError(e.to_string()))
+            }
+        }
+    }
+
     /// Fallback health status when Ryzanstein is unavailable
     pub fn fallback_health() -> HealthStatus {
         HealthStatus {
@@ -76,6 +120,26 @@ mod tests {
         assert_eq!(client.base_url, "http://localhost:8000");
     }
 
+    #[tokio::test]
+    async fn test_calls_traced_as_client_spans() {
+        let collector = crate::test_support::HttpCollector::start();
+        let telemetry = Arc::new(SigmaTelemetry::new(TelemetryConfig::default()));
+        let config = TelemetryConfig {
+            ryzanstein_url: collector.endpoint.clone(),
+            ..TelemetryConfig::default()
+        };
+        let client = RyzansteinTelemetryClient::new(&config).with_telemetry(Arc::clone(&telemetry));
+        client.push_metrics(&telemetry.snapshot()).await.unwrap();
+
+        let span = telemetry.drain_spans(usize::MAX).pop().unwrap();
+        assert_eq!(span.kind, crate::SpanKind::Client);
+        assert_eq!(span.name, "POST");
+        assert!(matches!(span.status, crate::SpanStatus::Ok));
+        let request = &collector.requests_to("/v1/telemetry")[0];
+        let traceparent = request.traceparent.as_deref().expect("traceparent injected");
+        assert_eq!(traceparent, format!("00-{}-{}-01", span.trace_id, span.span_id));
+    }
+
     #[test]
     fn test_fallback_health() {
         let health = RyzansteinTelemetryClient::fallback_health();
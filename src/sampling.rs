@@ -0,0 +1,226 @@
+//! Head sampling decisions made when a span starts.
+
+use crate::config::{SamplerKind, TelemetryConfig};
+use crate::{SpanContext, SpanOperation, TraceId};
+
+/// Outcome of a sampling decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingDecision {
+    /// Span is not recorded and propagates as not sampled
+    Drop,
+    /// Span is recorded, exported, and propagates as sampled
+    RecordAndSample,
+}
+
+/// Decides whether a new span is recorded.
+///
+/// Consulted for root spans and for spans continuing a remote parent. Local
+/// child spans always inherit their parent's decision so traces stay whole.
+pub trait Sampler: Send + Sync {
+    fn should_sample(
+        &self,
+        parent: Option<&SpanContext>,
+        trace_id: TraceId,
+        name: &str,
+        operation: &SpanOperation,
+    ) -> SamplingDecision;
+
+    /// Short description, e.g. `TraceIdRatioBased{0.25}`
+    fn description(&self) -> String;
+}
+
+/// Samples every span
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysOn;
+
+/// Samples no spans
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysOff;
+
+/// Samples a deterministic fraction of traces based on the trace ID, so every
+/// service using the same ratio makes the same decision for a trace
+#[derive(Debug, Clone, Copy)]
+pub struct TraceIdRatio {
+    ratio: f64,
+    bound: u64,
+}
+
+/// Follows the parent's sampled flag, and delegates root spans to `root`
+pub struct ParentBased {
+    root: Box<dyn Sampler>,
+}
+
+impl Sampler for AlwaysOn {
+    fn should_sample(&self, _: Option<&SpanContext>, _: TraceId, _: &str, _: &SpanOperation) -> SamplingDecision {
+        SamplingDecision::RecordAndSample
+    }
+
+    fn description(&self) -> String {
+        "AlwaysOnSampler".to_string()
+    }
+}
+
+impl Sampler for AlwaysOff {
+    fn should_sample(&self, _: Option<&SpanContext>, _: TraceId, _: &str, _: &SpanOperation) -> SamplingDecision {
+        SamplingDecision::Drop
+    }
+
+    fn description(&self) -> String {
+        "AlwaysOffSampler".to_string()
+    }
+}
+
+impl TraceIdRatio {
+    /// `ratio` is clamped to 0.0..=1.0
+    pub fn new(ratio: f64) -> Self {
+        let ratio = clamp_ratio(ratio);
+        Self {
+            ratio,
+            bound: ratio_bound(ratio),
+        }
+    }
+}
+
+/// Whether `trace_id` is among the `ratio` share of trace IDs that
+/// [`TraceIdRatio`] samples, without building a sampler
+pub fn trace_id_ratio_accepts(trace_id: TraceId, ratio: f64) -> bool {
+    let ratio = clamp_ratio(ratio);
+    ratio >= 1.0 || below_bound(trace_id, ratio_bound(ratio))
+}
+
+fn clamp_ratio(ratio: f64) -> f64 {
+    if ratio.is_nan() {
+        0.0
+    } else {
+        ratio.clamp(0.0, 1.0)
+    }
+}
+
+fn ratio_bound(ratio: f64) -> u64 {
+    (ratio * (1u64 << 63) as f64) as u64
+}
+
+/// Compare the low 63 bits of the random trace ID against the ratio's bound
+fn below_bound(trace_id: TraceId, bound: u64) -> bool {
+    let bytes = trace_id.to_bytes();
+    let mut low = [0u8; 8];
+    low.copy_from_slice(&bytes[8..]);
+    u64::from_be_bytes(low) >> 1 < bound
+}
+
+impl Sampler for TraceIdRatio {
+    fn should_sample(&self, _: Option<&SpanContext>, trace_id: TraceId, _: &str, _: &SpanOperation) -> SamplingDecision {
+        if self.ratio >= 1.0 || below_bound(trace_id, self.bound) {
+            SamplingDecision::RecordAndSample
+        } else {
+            SamplingDecision::Drop
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("TraceIdRatioBased{{{}}}", self.ratio)
+    }
+}
+
+impl ParentBased {
+    pub fn new(root: impl Sampler + 'static) -> Self {
+        Self { root: Box::new(root) }
+    }
+}
+
+impl Sampler for ParentBased {
+    fn should_sample(
+        &self,
+        parent: Option<&SpanContext>,
+        trace_id: TraceId,
+        name: &str,
+        operation: &SpanOperation,
+    ) -> SamplingDecision {
+        match parent {
+            Some(p) if p.trace_flags.is_sampled() => SamplingDecision::RecordAndSample,
+            Some(_) => SamplingDecision::Drop,
+            None => self.root.should_sample(None, trace_id, name, operation),
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("ParentBased{{root:{}}}", self.root.description())
+    }
+}
+
+/// Build the sampler selected by `config.sampler`, using `config.sampling_rate`
+/// as the ratio where one applies
+pub fn from_config(config: &TelemetryConfig) -> Box<dyn Sampler> {
+    match config.sampler {
+        SamplerKind::AlwaysOn => Box::new(AlwaysOn),
+        SamplerKind::AlwaysOff => Box::new(AlwaysOff),
+        SamplerKind::TraceIdRatio => Box::new(TraceIdRatio::new(config.sampling_rate)),
+        SamplerKind::ParentBasedTraceIdRatio => Box::new(ParentBased::new(TraceIdRatio::new(config.sampling_rate))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SpanId, TraceFlags};
+
+    fn decide(sampler: &dyn Sampler, parent: Option<&SpanContext>) -> SamplingDecision {
+        sampler.should_sample(parent, TraceId::random(), "op", &SpanOperation::Inference)
+    }
+
+    #[test]
+    fn test_always_on_off() {
+        assert_eq!(decide(&AlwaysOn, None), SamplingDecision::RecordAndSample);
+        assert_eq!(decide(&AlwaysOff, None), SamplingDecision::Drop);
+    }
+
+    #[test]
+    fn test_trace_id_ratio_extremes() {
+        for _ in 0..100 {
+            assert_eq!(decide(&TraceIdRatio::new(1.0), None), SamplingDecision::RecordAndSample);
+            assert_eq!(decide(&TraceIdRatio::new(0.0), None), SamplingDecision::Drop);
+        }
+    }
+
+    #[test]
+    fn test_trace_id_ratio_approximate() {
+        let sampler = TraceIdRatio::new(0.25);
+        let sampled = (0..10_000)
+            .filter(|_| decide(&sampler, None) == SamplingDecision::RecordAndSample)
+            .count();
+        assert!((2_000..3_000).contains(&sampled), "sampled {sampled}");
+    }
+
+    #[test]
+    fn test_trace_id_ratio_deterministic() {
+        let sampler = TraceIdRatio::new(0.5);
+        let id = TraceId::random();
+        let first = sampler.should_sample(None, id, "a", &SpanOperation::Inference);
+        for _ in 0..10 {
+            assert_eq!(sampler.should_sample(None, id, "b", &SpanOperation::ModelLoad), first);
+        }
+    }
+
+    #[test]
+    fn test_trace_id_ratio_accepts_matches_sampler() {
+        let sampler = TraceIdRatio::new(0.3);
+        for _ in 0..1_000 {
+            let id = TraceId::random();
+            let sampled = sampler.should_sample(None, id, "a", &SpanOperation::Inference) == SamplingDecision::RecordAndSample;
+            assert_eq!(trace_id_ratio_accepts(id, 0.3), sampled);
+        }
+        assert!(trace_id_ratio_accepts(TraceId::random(), 2.0));
+        assert!(!trace_id_ratio_accepts(TraceId::random(), f64::NAN));
+    }
+
+    #[test]
+    fn test_parent_based_follows_parent() {
+        let sampler = ParentBased::new(AlwaysOff);
+        let mut parent = SpanContext::new(TraceId::random(), SpanId::random());
+        assert_eq!(decide(&sampler, Some(&parent)), SamplingDecision::RecordAndSample);
+        parent.trace_flags = TraceFlags::NOT_SAMPLED;
+        assert_eq!(decide(&sampler, Some(&parent)), SamplingDecision::Drop);
+        assert_eq!(decide(&sampler, None), SamplingDecision::Drop);
+        assert_eq!(sampler.description(), "ParentBased{root:AlwaysOffSampler}");
+    }
+}
@@ -1,72 +1,114 @@
 //! Well-known span definitions for Ryzanstein operations.
 
-use crate::SpanOperation;
+use crate::{SpanKind, SpanOperation, SpanOptions};
 
-/// Pre-defined span templates for common Ryzanstein operations
+/// Pre-defined span templates for common Ryzanstein operations.
+///
+/// Each returns the operation plus [`SpanOptions`] carrying the span kind and
+/// attributes, ready for [`crate::SigmaTelemetry::start_span_with_options`].
 pub struct SpanTemplates;
 
 impl SpanTemplates {
     /// Inference request span with model and token attributes
-    pub fn inference(model: &str, max_tokens: usize) -> (SpanOperation, Vec<(&'static str, String)>) {
+    pub fn inference(model: &str, max_tokens: usize) -> (SpanOperation, SpanOptions) {
         (
             SpanOperation::Inference,
-            vec![
-                ("model.name", model.to_string()),
-                ("model.max_tokens", max_tokens.to_string()),
-            ],
+            SpanOptions::new()
+                .with_attribute("model.name", model)
+                .with_attribute("model.max_tokens", max_tokens),
         )
     }
 
     /// Model loading span
-    pub fn model_load(model: &str, size_mb: f64) -> (SpanOperation, Vec<(&'static str, String)>) {
+    pub fn model_load(model: &str, size_mb: f64) -> (SpanOperation, SpanOptions) {
         (
             SpanOperation::ModelLoad,
-            vec![
-                ("model.name", model.to_string()),
-                ("model.size_mb", format!("{:.1}", size_mb)),
-            ],
+            SpanOptions::new()
+                .with_attribute("model.name", model)
+                .with_attribute("model.size_mb", size_mb),
         )
     }
 
     /// KV cache operation span
-    pub fn kv_cache(operation: &str, layer: usize) -> (SpanOperation, Vec<(&'static str, String)>) {
+    pub fn kv_cache(operation: &str, layer: usize) -> (SpanOperation, SpanOptions) {
         (
             SpanOperation::KvCacheOp,
-            vec![
-                ("kv.operation", operation.to_string()),
-                ("kv.layer", layer.to_string()),
-            ],
+            SpanOptions::new()
+                .with_attribute("kv.operation", operation)
+                .with_attribute("kv.layer", layer),
         )
     }
 
     /// Speculative decoding draft span
-    pub fn speculative_draft(draft_tokens: usize) -> (SpanOperation, Vec<(&'static str, String)>) {
+    pub fn speculative_draft(draft_tokens: usize) -> (SpanOperation, SpanOptions) {
         (
             SpanOperation::SpeculativeDraft,
-            vec![("speculative.draft_tokens", draft_tokens.to_string())],
+            SpanOptions::new().with_attribute("speculative.draft_tokens", draft_tokens),
         )
     }
 
     /// Speculative decoding verification span
-    pub fn speculative_verify(accepted: usize, total: usize) -> (SpanOperation, Vec<(&'static str, String)>) {
+    pub fn speculative_verify(accepted: usize, total: usize) -> (SpanOperation, SpanOptions) {
         (
             SpanOperation::SpeculativeVerify,
-            vec![
-                ("speculative.accepted", accepted.to_string()),
-                ("speculative.total", total.to_string()),
-                ("speculative.acceptance_rate", format!("{:.2}", accepted as f64 / total as f64)),
-            ],
+            SpanOptions::new()
+                .with_attribute("speculative.accepted", accepted)
+                .with_attribute("speculative.total", total)
+                .with_attribute("speculative.acceptance_rate", accepted as f64 / total as f64),
         )
     }
 
     /// Agent execution span
-    pub fn agent_execute(agent_id: &str, capability: &str) -> (SpanOperation, Vec<(&'static str, String)>) {
+    pub fn agent_execute(agent_id: &str, capability: &str) -> (SpanOperation, SpanOptions) {
         (
             SpanOperation::AgentExecute,
-            vec![
-                ("agent.id", agent_id.to_string()),
-                ("agent.capability", capability.to_string()),
-            ],
+            SpanOptions::new()
+                .with_attribute("agent.id", agent_id)
+                .with_attribute("agent.capability", capability),
+        )
+    }
+
+    /// Server span for an incoming HTTP request
+    pub fn http_server(method: &str, route: &str) -> (SpanOperation, SpanOptions) {
+        (
+            SpanOperation::Custom("http.server".to_string()),
+            SpanOptions::new()
+                .with_kind(SpanKind::Server)
+                .with_attribute("http.request.method", method)
+                .with_attribute("http.route", route),
+        )
+    }
+
+    /// Client span for an outgoing HTTP request, e.g. to Ryzanstein
+    pub fn http_client(method: &str, url: &str) -> (SpanOperation, SpanOptions) {
+        (
+            SpanOperation::Custom("http.client".to_string()),
+            SpanOptions::new()
+                .with_kind(SpanKind::Client)
+                .with_attribute("http.request.method", method)
+                .with_attribute("url.full", url),
+        )
+    }
+
+    /// Producer span for publishing a message to `queue`
+    pub fn queue_publish(queue: &str) -> (SpanOperation, SpanOptions) {
+        (
+            SpanOperation::Custom("queue.publish".to_string()),
+            SpanOptions::new()
+                .with_kind(SpanKind::Producer)
+                .with_attribute("messaging.destination.name", queue)
+                .with_attribute("messaging.operation", "publish"),
+        )
+    }
+
+    /// Consumer span for processing a message received from `queue`
+    pub fn queue_process(queue: &str) -> (SpanOperation, SpanOptions) {
+        (
+            SpanOperation::Custom("queue.process".to_string()),
+            SpanOptions::new()
+                .with_kind(SpanKind::Consumer)
+                .with_attribute("messaging.destination.name", queue)
+                .with_attribute("messaging.operation", "process"),
         )
     }
 }
@@ -74,32 +116,42 @@ impl SpanTemplates {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::AttributeValue;
 
     #[test]
     fn test_inference_template() {
-        let (op, attrs) = SpanTemplates::inference("bitnet-3b", 1024);
+        let (op, options) = SpanTemplates::inference("bitnet-3b", 1024);
         assert_eq!(op, SpanOperation::Inference);
-        assert_eq!(attrs.len(), 2);
-        assert_eq!(attrs[0].1, "bitnet-3b");
+        assert_eq!(options.kind, SpanKind::Internal);
+        assert_eq!(options.attributes.len(), 2);
+        assert_eq!(options.attributes.get("model.name").unwrap(), &"bitnet-3b");
     }
 
     #[test]
     fn test_model_load_template() {
-        let (op, attrs) = SpanTemplates::model_load("mamba-2.8b", 5600.0);
+        let (op, options) = SpanTemplates::model_load("mamba-2.8b", 5600.0);
         assert_eq!(op, SpanOperation::ModelLoad);
-        assert_eq!(attrs[1].1, "5600.0");
+        assert_eq!(options.attributes.get("model.size_mb"), Some(&AttributeValue::Double(5600.0)));
     }
 
     #[test]
     fn test_speculative_verify_acceptance() {
-        let (_, attrs) = SpanTemplates::speculative_verify(8, 10);
-        assert_eq!(attrs[2].1, "0.80");
+        let (_, options) = SpanTemplates::speculative_verify(8, 10);
+        assert_eq!(options.attributes.get("speculative.acceptance_rate"), Some(&AttributeValue::Double(0.8)));
     }
 
     #[test]
     fn test_agent_execute_template() {
-        let (op, attrs) = SpanTemplates::agent_execute("agent-001", "code_review");
+        let (op, options) = SpanTemplates::agent_execute("agent-001", "code_review");
         assert_eq!(op, SpanOperation::AgentExecute);
-        assert_eq!(attrs[0].1, "agent-001");
+        assert_eq!(options.attributes.get("agent.id").unwrap(), &"agent-001");
+    }
+
+    #[test]
+    fn test_remote_templates_set_kind() {
+        assert_eq!(SpanTemplates::http_server("POST", "/v1/generate").1.kind, SpanKind::Server);
+        assert_eq!(SpanTemplates::http_client("GET", "http://localhost:8000/health").1.kind, SpanKind::Client);
+        assert_eq!(SpanTemplates::queue_publish("jobs").1.kind, SpanKind::Producer);
+        assert_eq!(SpanTemplates::queue_process("jobs").1.kind, SpanKind::Consumer);
     }
 }
@@ -0,0 +1,351 @@
+//! Tail sampling: keep or drop whole traces once they have finished.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sampling::trace_id_ratio_accepts;
+use crate::{SpanRecord, SpanStatus, TraceId};
+
+/// Tail sampling configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TailSamplingConfig {
+    /// How long to wait for a trace's local root before deciding anyway
+    pub decision_wait_secs: u64,
+    /// Maximum traces buffered at once; the oldest is decided early when
+    /// exceeded. Verdicts for as many recently decided traces are kept, so
+    /// spans finishing after their root follow the trace's decision.
+    pub max_traces: usize,
+    /// A trace is kept when any policy matches
+    pub policies: Vec<TailPolicy>,
+}
+
+impl Default for TailSamplingConfig {
+    fn default() -> Self {
+        Self {
+            decision_wait_secs: 30,
+            max_traces: 10_000,
+            policies: vec![TailPolicy::Error],
+        }
+    }
+}
+
+/// Rule that marks a finished trace as worth keeping
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TailPolicy {
+    /// Any span ended with `SpanStatus::Error`
+    Error,
+    /// A span of `operation` (its display name, e.g. "kv_cache.op") took at
+    /// least `threshold_ms`
+    Latency { operation: String, threshold_ms: u64 },
+    /// A span carries attribute `key` whose value displays as `value`
+    Attribute { key: String, value: String },
+    /// A `ratio` share of traces, chosen by trace ID
+    Probabilistic { ratio: f64 },
+}
+
+impl TailPolicy {
+    fn matches(&self, trace_id: TraceId, spans: &[SpanRecord]) -> bool {
+        match self {
+            TailPolicy::Error => spans.iter().any(|s| matches!(s.status, SpanStatus::Error(_))),
+            TailPolicy::Latency { operation, threshold_ms } => {
+                let threshold = Duration::from_millis(*threshold_ms);
+                spans.iter().any(|s| {
+                    s.duration.is_some_and(|d| d >= threshold) && s.operation.to_string() == *operation
+                })
+            }
+            TailPolicy::Attribute { key, value } => spans
+                .iter()
+                .any(|s| s.attributes.get(key).is_some_and(|v| v.to_string() == *value)),
+            TailPolicy::Probabilistic { ratio } => trace_id_ratio_accepts(trace_id, *ratio),
+        }
+    }
+}
+
+struct PendingTrace {
+    first_seen: Instant,
+    /// Key in [`State::pending_order`]
+    seq: u64,
+    spans: Vec<SpanRecord>,
+}
+
+/// Buffers finished spans per trace and releases only kept traces
+pub(crate) struct TailSampler {
+    config: TailSamplingConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: HashMap<TraceId, PendingTrace>,
+    /// `pending` keys by arrival, so the oldest trace is found without a scan
+    pending_order: BTreeMap<u64, TraceId>,
+    next_seq: u64,
+    /// Whether each recently decided trace was kept, for late spans
+    decided: HashMap<TraceId, bool>,
+    /// `decided` keys, oldest decision first
+    decided_order: VecDeque<TraceId>,
+}
+
+impl State {
+    fn take_pending(&mut self, trace_id: TraceId) -> PendingTrace {
+        let trace = self.pending.remove(&trace_id).unwrap();
+        self.pending_order.remove(&trace.seq);
+        trace
+    }
+}
+
+/// Spans released by a decision, plus how many traces were kept and dropped
+#[derive(Default)]
+pub(crate) struct TailOutcome {
+    pub(crate) spans: Vec<SpanRecord>,
+    pub(crate) kept: u64,
+    pub(crate) dropped: u64,
+}
+
+impl TailSampler {
+    pub(crate) fn new(config: TailSamplingConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Buffer a finished span; decide its trace if it was the local root. A
+    /// span of an already decided trace is released or dropped with it.
+    pub(crate) fn offer(&self, span: SpanRecord) -> TailOutcome {
+        let mut outcome = TailOutcome::default();
+        let mut state = self.state.lock().unwrap();
+        let is_local_root = span.parent_span_id.is_none() || span.parent_is_remote;
+        let trace_id = span.trace_id;
+
+        if let Some(&kept) = state.decided.get(&trace_id) {
+            if kept {
+                outcome.spans.push(span);
+            }
+            return outcome;
+        }
+        if !state.pending.contains_key(&trace_id) {
+            if state.pending.len() >= self.config.max_traces.max(1) {
+                if let Some((_, id)) = state.pending_order.pop_first() {
+                    let trace = state.pending.remove(&id).unwrap();
+                    self.decide(&mut state, id, trace.spans, &mut outcome);
+                }
+            }
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.pending_order.insert(seq, trace_id);
+            state.pending.insert(
+                trace_id,
+                PendingTrace {
+                    first_seen: Instant::now(),
+                    seq,
+                    spans: Vec::new(),
+                },
+            );
+        }
+        state.pending.get_mut(&trace_id).unwrap().spans.push(span);
+
+        if is_local_root {
+            let trace = state.take_pending(trace_id);
+            self.decide(&mut state, trace_id, trace.spans, &mut outcome);
+        }
+        outcome
+    }
+
+    /// Decide traces waiting longer than `decision_wait_secs`, or all of them
+    pub(crate) fn flush(&self, all: bool) -> TailOutcome {
+        let mut outcome = TailOutcome::default();
+        let wait = Duration::from_secs(self.config.decision_wait_secs);
+        let mut state = self.state.lock().unwrap();
+        // Oldest first, so stop at the first trace still within its wait
+        while let Some((_, &id)) = state.pending_order.first_key_value() {
+            if !all && state.pending[&id].first_seen.elapsed() < wait {
+                break;
+            }
+            let trace = state.take_pending(id);
+            self.decide(&mut state, id, trace.spans, &mut outcome);
+        }
+        outcome
+    }
+
+    fn decide(&self, state: &mut State, trace_id: TraceId, spans: Vec<SpanRecord>, outcome: &mut TailOutcome) {
+        let kept = self.config.policies.iter().any(|p| p.matches(trace_id, &spans));
+        if kept {
+            outcome.kept += 1;
+            outcome.spans.extend(spans);
+        } else {
+            outcome.dropped += 1;
+        }
+        if state.decided_order.len() >= self.config.max_traces.max(1) {
+            if let Some(oldest) = state.decided_order.pop_front() {
+                state.decided.remove(&oldest);
+            }
+        }
+        state.decided.insert(trace_id, kept);
+        state.decided_order.push_back(trace_id);
+    }
+
+    pub(crate) fn pending_traces(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryConfig;
+    use crate::{SigmaTelemetry, SpanOperation};
+
+    fn telemetry(policies: Vec<TailPolicy>) -> SigmaTelemetry {
+        SigmaTelemetry::new(TelemetryConfig {
+            tail_sampling: Some(TailSamplingConfig {
+                policies,
+                ..TailSamplingConfig::default()
+            }),
+            ..TelemetryConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_error_trace_kept_whole() {
+        let t = telemetry(vec![TailPolicy::Error]);
+        let root = t.start_span("req", SpanOperation::Inference);
+        root.child_span("decode", SpanOperation::TokenGeneration).set_error("nan logits");
+        assert_eq!(t.snapshot().span_count, 0, "trace must wait for its root");
+        assert_eq!(t.snapshot().pending_traces, 1);
+        root.set_ok();
+        assert_eq!(t.snapshot().span_count, 2);
+
+        t.start_span("healthy", SpanOperation::Inference).set_ok();
+        assert_eq!(t.snapshot().span_count, 2);
+        assert_eq!(t.metrics().get_counter("traces.tail_dropped"), 1);
+    }
+
+    #[test]
+    fn test_child_finishing_after_root_follows_verdict() {
+        let t = std::sync::Arc::new(telemetry(vec![TailPolicy::Attribute {
+            key: "tenant".to_string(),
+            value: "vip".to_string(),
+        }]));
+        let mut kept = t.start_owned_span("req", SpanOperation::Inference);
+        kept.set_attribute("tenant", "vip");
+        let late_kept = kept.child_span("stream", SpanOperation::TokenGeneration);
+        let dropped = t.start_owned_span("req", SpanOperation::Inference);
+        let late_dropped = dropped.child_span("stream", SpanOperation::TokenGeneration);
+        kept.set_ok();
+        dropped.set_ok();
+        assert_eq!(t.snapshot().span_count, 1);
+
+        late_kept.set_ok();
+        late_dropped.set_ok();
+        assert_eq!(t.snapshot().span_count, 2);
+        assert_eq!(t.snapshot().pending_traces, 0, "late spans start no new trace");
+        assert_eq!(t.metrics().get_counter("traces.tail_kept"), 1);
+        assert_eq!(t.metrics().get_counter("traces.tail_dropped"), 1);
+    }
+
+    #[test]
+    fn test_latency_policy_per_operation() {
+        let policy = TailPolicy::Latency {
+            operation: "kv_cache.op".to_string(),
+            threshold_ms: 0,
+        };
+        let t = telemetry(vec![policy]);
+        t.start_span("load", SpanOperation::ModelLoad).set_ok();
+        assert_eq!(t.snapshot().span_count, 0);
+        t.start_span("evict", SpanOperation::KvCacheOp).set_ok();
+        assert_eq!(t.snapshot().span_count, 1);
+    }
+
+    #[test]
+    fn test_attribute_policy() {
+        let t = telemetry(vec![TailPolicy::Attribute {
+            key: "tenant".to_string(),
+            value: "vip".to_string(),
+        }]);
+        let mut span = t.start_span("req", SpanOperation::Inference);
+        span.set_attribute("tenant", "vip");
+        span.set_ok();
+        t.start_span("req", SpanOperation::Inference).set_ok();
+        assert_eq!(t.snapshot().span_count, 1);
+    }
+
+    #[test]
+    fn test_probabilistic_policy_extremes() {
+        let keep_all = telemetry(vec![TailPolicy::Probabilistic { ratio: 1.0 }]);
+        let keep_none = telemetry(vec![TailPolicy::Probabilistic { ratio: 0.0 }]);
+        for _ in 0..10 {
+            keep_all.start_span("req", SpanOperation::Inference).set_ok();
+            keep_none.start_span("req", SpanOperation::Inference).set_ok();
+        }
+        assert_eq!(keep_all.snapshot().span_count, 10);
+        assert_eq!(keep_none.snapshot().span_count, 0);
+    }
+
+    #[test]
+    fn test_orphaned_trace_decided_on_timeout() {
+        let sampler = TailSampler::new(TailSamplingConfig {
+            decision_wait_secs: 0,
+            ..TailSamplingConfig::default()
+        });
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        let root = t.start_span("req", SpanOperation::Inference);
+        root.child_span("decode", SpanOperation::TokenGeneration).set_error("oom");
+        let child = t.drain_spans(1).remove(0);
+
+        assert!(sampler.offer(child).spans.is_empty());
+        assert_eq!(sampler.pending_traces(), 1);
+        let outcome = sampler.flush(false);
+        assert_eq!((outcome.kept, outcome.spans.len()), (1, 1));
+        assert_eq!(sampler.pending_traces(), 0);
+    }
+
+    #[test]
+    fn test_max_traces_evicts_oldest() {
+        let sampler = TailSampler::new(TailSamplingConfig {
+            max_traces: 1,
+            ..TailSamplingConfig::default()
+        });
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        let a = t.start_span("a", SpanOperation::Inference);
+        let b = t.start_span("b", SpanOperation::Inference);
+        a.child_span("a1", SpanOperation::KvCacheOp).set_error("x");
+        b.child_span("b1", SpanOperation::KvCacheOp).set_ok();
+        let spans = t.drain_spans(2);
+
+        assert!(sampler.offer(spans[0].clone()).spans.is_empty());
+        let outcome = sampler.offer(spans[1].clone());
+        assert_eq!(outcome.spans[0].name, "a1");
+        assert_eq!(sampler.pending_traces(), 1);
+    }
+
+    #[test]
+    fn test_eviction_skips_decided_traces() {
+        let sampler = TailSampler::new(TailSamplingConfig {
+            max_traces: 2,
+            ..TailSamplingConfig::default()
+        });
+        let t = SigmaTelemetry::new(TelemetryConfig::default());
+        let root = |name| t.start_span(name, SpanOperation::Inference);
+        let (a, b, c, d) = (root("a"), root("b"), root("c"), root("d"));
+        for parent in [&a, &b, &c, &d] {
+            parent.child_span("child", SpanOperation::KvCacheOp).set_error("x");
+        }
+        let children = t.drain_spans(4);
+
+        sampler.offer(children[0].clone());
+        sampler.offer(children[1].clone());
+        // The root decides trace a, leaving b as the oldest pending trace
+        a.set_ok();
+        assert_eq!(sampler.offer(t.drain_spans(1).remove(0)).kept, 1);
+        assert!(sampler.offer(children[2].clone()).spans.is_empty());
+        let outcome = sampler.offer(children[3].clone());
+        assert_eq!(outcome.spans.len(), 1);
+        assert_eq!(outcome.spans[0].trace_id, children[1].trace_id, "b evicted before c");
+        assert_eq!(sampler.pending_traces(), 2);
+    }
+}
@@ -0,0 +1,335 @@
+//! Aggregation temporality and the collection cycle.
+//!
+//! The collector only keeps cumulative state. Each consumer collects through
+//! its own [`MetricReader`]; a delta reader takes a cumulative snapshot,
+//! subtracts the one it took at its previous collection and keeps the new
+//! one as the checkpoint for the next, so readers never move each other's
+//! baseline.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ExponentialBuckets, ExponentialHistogramData, HistogramData, MetricAttributes, MetricsCollector, MetricsData, Series,
+};
+
+/// Whether exported sums and histograms cover everything since the
+/// collector started or only the interval since the previous collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Temporality {
+    /// Totals since the collector started, as Prometheus expects
+    #[default]
+    Cumulative,
+    /// Change since the reader's previous collection, for backends that
+    /// aggregate over time themselves
+    Delta,
+}
+
+/// One consumer's view of a [`MetricsCollector`], collected with
+/// [`MetricsCollector::collect`]. A delta reader keeps its own checkpoint
+/// and histogram extremes, so any number of readers can collect deltas from
+/// one collector. Readers are meant to live as long as their consumer: each
+/// delta reader adds a few bytes to every histogram it has collected.
+#[derive(Debug)]
+pub struct MetricReader {
+    temporality: Temporality,
+    /// Identifies the reader's histogram extremes
+    id: u64,
+    checkpoint: Mutex<DeltaCheckpoint>,
+}
+
+impl MetricReader {
+    pub fn new(temporality: Temporality) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            temporality,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            checkpoint: Mutex::new(DeltaCheckpoint::default()),
+        }
+    }
+
+    pub fn temporality(&self) -> Temporality {
+        self.temporality
+    }
+
+    pub(crate) fn collect(&self, metrics: &MetricsCollector) -> MetricsData {
+        match self.temporality {
+            Temporality::Cumulative => metrics.cumulative_data(None),
+            Temporality::Delta => {
+                let mut checkpoint = self.checkpoint.lock().unwrap();
+                checkpoint.delta(metrics.cumulative_data(Some(self.id)))
+            }
+        }
+    }
+}
+
+type SeriesKey = (String, MetricAttributes);
+
+/// Cumulative values as of a reader's previous delta collection
+#[derive(Debug, Default)]
+struct DeltaCheckpoint {
+    /// `None` before the first collection, which starts with the collector
+    time_unix_nano: Option<u64>,
+    counters: HashMap<SeriesKey, u64>,
+    up_down_counters: HashMap<SeriesKey, i64>,
+    histograms: HashMap<SeriesKey, HistogramData>,
+    exponential_histograms: HashMap<SeriesKey, ExponentialHistogramData>,
+}
+
+impl DeltaCheckpoint {
+    /// The change from the checkpoint to `cumulative`, which becomes the
+    /// new checkpoint. Histogram min and max are taken from `cumulative`
+    /// as is, so should already cover only the interval. Series without
+    /// new measurements are left out; gauges pass through.
+    fn delta(&mut self, cumulative: MetricsData) -> MetricsData {
+        let start_time_unix_nano = self
+            .time_unix_nano
+            .replace(cumulative.time_unix_nano)
+            .unwrap_or(cumulative.start_time_unix_nano);
+        MetricsData {
+            start_time_unix_nano,
+            time_unix_nano: cumulative.time_unix_nano,
+            temporality: Temporality::Delta,
+            // A total below the checkpoint means an observed counter restarted
+            counters: diff(&mut self.counters, cumulative.counters, |current, previous| match previous {
+                Some(&previous) if *current >= previous => Some(current - previous).filter(|&d| d > 0),
+                _ => Some(*current).filter(|&d| d > 0),
+            }),
+            gauges: cumulative.gauges,
+            up_down_counters: diff(&mut self.up_down_counters, cumulative.up_down_counters, |current, previous| {
+                Some(current - previous.copied().unwrap_or(0)).filter(|&d| d != 0)
+            }),
+            histograms: diff(&mut self.histograms, cumulative.histograms, histogram_delta),
+            exponential_histograms: diff(
+                &mut self.exponential_histograms,
+                cumulative.exponential_histograms,
+                exponential_delta,
+            ),
+        }
+    }
+}
+
+/// `delta` of each series against its previous value, dropping series for
+/// which it returns `None`. `previous` is replaced by the current values,
+/// so series no longer reported start afresh if they return.
+fn diff<T>(
+    previous: &mut HashMap<SeriesKey, T>,
+    current: Vec<Series<T>>,
+    delta: impl Fn(&T, Option<&T>) -> Option<T>,
+) -> Vec<Series<T>> {
+    let mut checkpoint = HashMap::with_capacity(current.len());
+    let mut series = Vec::new();
+    for Series { name, attributes, value } in current {
+        let key = (name, attributes);
+        if let Some(delta) = delta(&value, previous.get(&key)) {
+            series.push(Series::new(key.0.clone(), key.1.clone(), delta));
+        }
+        checkpoint.insert(key, value);
+    }
+    *previous = checkpoint;
+    series
+}
+
+fn histogram_delta(current: &HistogramData, previous: Option<&HistogramData>) -> Option<HistogramData> {
+    let Some(previous) = previous.filter(|p| p.bounds == current.bounds && p.count <= current.count) else {
+        return Some(current.clone()).filter(|h| h.count > 0);
+    };
+    if current.count == previous.count {
+        return None;
+    }
+    Some(HistogramData {
+        count: current.count - previous.count,
+        sum: current.sum - previous.sum,
+        min: current.min,
+        max: current.max,
+        bounds: current.bounds.clone(),
+        // Saturating as a sketch collapsing its lowest buckets can move
+        // earlier values onto a neighbouring bound
+        bucket_counts: current
+            .bucket_counts
+            .iter()
+            .zip(&previous.bucket_counts)
+            .map(|(c, p)| c.saturating_sub(*p))
+            .collect(),
+    })
+}
+
+/// Exponential histograms only ever lower their scale, so the previous
+/// buckets are downscaled to the current scale before subtracting
+fn exponential_delta(
+    current: &ExponentialHistogramData,
+    previous: Option<&ExponentialHistogramData>,
+) -> Option<ExponentialHistogramData> {
+    let Some(previous) = previous.filter(|p| p.scale >= current.scale && p.count <= current.count) else {
+        return Some(current.clone()).filter(|h| h.count > 0);
+    };
+    if current.count == previous.count {
+        return None;
+    }
+    let change = (previous.scale - current.scale) as u32;
+    Some(ExponentialHistogramData {
+        count: current.count - previous.count,
+        sum: current.sum - previous.sum,
+        min: current.min,
+        max: current.max,
+        scale: current.scale,
+        zero_count: current.zero_count.saturating_sub(previous.zero_count),
+        positive: subtract_buckets(&current.positive, &previous.positive, change),
+        negative: subtract_buckets(&current.negative, &previous.negative, change),
+    })
+}
+
+/// `current` less `previous`, whose indexes are first shifted down by
+/// `change` scales
+fn subtract_buckets(current: &ExponentialBuckets, previous: &ExponentialBuckets, change: u32) -> ExponentialBuckets {
+    let mut bucket_counts = current.bucket_counts.clone();
+    for (i, &count) in previous.bucket_counts.iter().enumerate() {
+        let index = (previous.offset + i as i32) >> change;
+        if let Some(c) = usize::try_from(index - current.offset).ok().and_then(|i| bucket_counts.get_mut(i)) {
+            *c = c.saturating_sub(count);
+        }
+    }
+    ExponentialBuckets {
+        offset: current.offset,
+        bucket_counts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::histogram::{HistogramAggregation, HistogramConfig};
+    use crate::MetricsCollector;
+
+    fn collector(aggregation: HistogramAggregation) -> MetricsCollector {
+        MetricsCollector::new(HistogramConfig {
+            default: aggregation,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_delta_counters_and_timestamps() {
+        let m = collector(HistogramAggregation::default());
+        let reader = MetricReader::new(Temporality::Delta);
+        m.increment_by("requests", 5);
+        m.add_up_down("active", 3);
+        m.set_gauge("utilization", 0.5);
+
+        let first = m.collect(&reader);
+        assert_eq!(first.temporality, Temporality::Delta);
+        assert_eq!(first.counters[0].value, 5);
+        assert_eq!(first.up_down_counters[0].value, 3);
+
+        m.increment_by("requests", 2);
+        m.add_up_down("active", -1);
+        let second = m.collect(&reader);
+        assert_eq!(second.start_time_unix_nano, first.time_unix_nano);
+        assert_eq!(second.counters[0].value, 2);
+        assert_eq!(second.up_down_counters[0].value, -1);
+        assert_eq!(second.gauges[0].value, 0.5, "gauges are not deltas");
+
+        let third = m.collect(&reader);
+        assert!(third.counters.is_empty() && third.up_down_counters.is_empty());
+
+        let cumulative = m.export_data();
+        assert_eq!(cumulative.temporality, Temporality::Cumulative);
+        assert_eq!(cumulative.counters[0].value, 7);
+        assert!(cumulative.start_time_unix_nano < first.time_unix_nano);
+    }
+
+    #[test]
+    fn test_delta_histograms() {
+        let m = collector(HistogramAggregation::ExplicitBuckets {
+            bounds: vec![10.0, 100.0],
+        });
+        let reader = MetricReader::new(Temporality::Delta);
+        [5.0, 50.0].into_iter().for_each(|v| m.record_histogram("latency", v));
+        m.collect(&reader);
+
+        m.record_histogram("latency", 500.0);
+        m.record_histogram("latency", 20.0);
+        let delta = m.collect(&reader);
+        let h = &delta.histograms[0].value;
+        assert_eq!((h.count, h.sum, h.min, h.max), (2, 520.0, 20.0, 500.0));
+        assert_eq!(h.bucket_counts, vec![0, 1, 1]);
+
+        let cumulative = &m.export_data().histograms[0].value;
+        assert_eq!((cumulative.count, cumulative.min), (4, 5.0));
+        assert!(m.collect(&reader).histograms.is_empty());
+    }
+
+    #[test]
+    fn test_delta_readers_are_independent() {
+        let m = collector(HistogramAggregation::ExplicitBuckets {
+            bounds: vec![10.0, 100.0],
+        });
+        let (otlp, other) = (MetricReader::new(Temporality::Delta), MetricReader::new(Temporality::Delta));
+        m.increment_by("requests", 5);
+        m.record_histogram("latency", 50.0);
+        assert_eq!(m.collect(&otlp).counters[0].value, 5);
+
+        m.increment_by("requests", 2);
+        m.record_histogram("latency", 5.0);
+        let first = m.collect(&other);
+        assert_eq!(first.counters[0].value, 7, "first collection covers everything so far");
+        let h = &first.histograms[0].value;
+        assert_eq!((h.count, h.min, h.max), (2, 5.0, 50.0));
+
+        m.increment_by("requests", 1);
+        m.record_histogram("latency", 500.0);
+        let second = m.collect(&otlp);
+        assert_eq!(second.counters[0].value, 3, "the other reader left this baseline alone");
+        let h = &second.histograms[0].value;
+        assert_eq!((h.count, h.min, h.max), (2, 5.0, 500.0));
+
+        let second = m.collect(&other);
+        assert_eq!(second.counters[0].value, 1);
+        let h = &second.histograms[0].value;
+        assert_eq!((h.count, h.min, h.max), (1, 500.0, 500.0));
+        assert_eq!(second.start_time_unix_nano, first.time_unix_nano);
+
+        let cumulative = m.collect(&MetricReader::new(Temporality::Cumulative));
+        assert_eq!(cumulative.counters[0].value, 8);
+        assert_eq!((cumulative.histograms[0].value.min, cumulative.histograms[0].value.max), (5.0, 500.0));
+    }
+
+    #[test]
+    fn test_delta_exponential_across_downscale() {
+        let m = collector(HistogramAggregation::Exponential {
+            max_size: 4,
+            max_scale: 20,
+        });
+        let reader = MetricReader::new(Temporality::Delta);
+        m.record_histogram("load_ms", 1.5);
+        let first = m.collect(&reader).exponential_histograms[0].value.clone();
+
+        // Far enough away to force a lower scale
+        m.record_histogram("load_ms", 1000.0);
+        let delta = m.collect(&reader).exponential_histograms[0].value.clone();
+        assert!(delta.scale < first.scale);
+        assert_eq!((delta.count, delta.min, delta.max), (1, 1000.0, 1000.0));
+        assert_eq!(delta.positive.bucket_counts.iter().sum::<u64>(), 1);
+        let bucket = delta.positive.offset
+            + delta.positive.bucket_counts.iter().position(|&c| c == 1).unwrap() as i32;
+        assert!(crate::histogram::lower_boundary(bucket, delta.scale) < 1000.0);
+        assert!(crate::histogram::lower_boundary(bucket + 1, delta.scale) >= 1000.0);
+    }
+
+    #[test]
+    fn test_delta_observed_counter_restart() {
+        let m = collector(HistogramAggregation::default());
+        let reader = MetricReader::new(Temporality::Delta);
+        let total = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(10));
+        let observed = std::sync::Arc::clone(&total);
+        m.observe_counter("restarts", move |o| o.observe(observed.load(std::sync::atomic::Ordering::Relaxed)));
+        assert_eq!(m.collect(&reader).counters[0].value, 10);
+
+        total.store(3, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(m.collect(&reader).counters[0].value, 3);
+    }
+}
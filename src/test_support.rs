@@ -0,0 +1,90 @@
+//! Test doubles shared across modules.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// Request captured by [`HttpCollector`]
+#[derive(Debug, Clone)]
+pub(crate) struct CapturedRequest {
+    pub(crate) path: String,
+    pub(crate) content_type: String,
+    /// W3C `traceparent` header, when sent
+    pub(crate) traceparent: Option<String>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl CapturedRequest {
+    pub(crate) fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// Minimal OTLP/HTTP collector on an ephemeral port that answers every
+/// request with 200 and records it
+pub(crate) struct HttpCollector {
+    pub(crate) endpoint: String,
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+}
+
+impl HttpCollector {
+    pub(crate) fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { return };
+                if let Some(request) = serve_one(stream) {
+                    captured.lock().unwrap().push(request);
+                }
+            }
+        });
+        Self { endpoint, requests }
+    }
+
+    pub(crate) fn requests(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Captured requests sent to `path`, e.g. "/v1/metrics"
+    pub(crate) fn requests_to(&self, path: &str) -> Vec<CapturedRequest> {
+        self.requests().into_iter().filter(|r| r.path == path).collect()
+    }
+}
+
+fn serve_one(stream: std::net::TcpStream) -> Option<CapturedRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let path = request_line.split_whitespace().nth(1)?.to_string();
+
+    let (mut content_type, mut length, mut traceparent) = (String::new(), 0, None);
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        match name.to_ascii_lowercase().as_str() {
+            "content-type" => content_type = value.trim().to_string(),
+            "content-length" => length = value.trim().parse().ok()?,
+            "traceparent" => traceparent = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    reader
+        .get_mut()
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+        .ok()?;
+    Some(CapturedRequest {
+        path,
+        content_type,
+        traceparent,
+        body,
+    })
+}